@@ -1,120 +1,83 @@
-use std::time::Duration;
-
-use reqwest::StatusCode;
+use scraper::CaseSensitivity;
 use scraper::Selector;
 
-use crate::cli::Language;
+use crate::cache::PageClass;
+use crate::error::{Error, Result};
+use crate::fetch::{FetchConfig, Fetcher};
+use crate::language::Language;
+use crate::source::{DubEpisodes, DubInfo, DubSource, DubStatus, DubbedAnime, ListedAnime};
+use crate::state;
 
-const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; rv:109.0) Gecko/20100101 Firefox/115.0";
+const SELECTOR_PAGE_INFO: &str = "div.pagenav-info";
+pub const ANIME_URL_PREFIX: &str = "https://anisearch.com/anime/";
+pub const DEFAULT_BASE_URL: &str = "https://www.anisearch.com";
 
-pub struct AnisearchClient<'a> {
-    client: reqwest::blocking::Client,
-    lang: &'a str,
+pub struct AnisearchClient {
+    fetcher: Fetcher,
+    language: Language,
+    base_url: String,
     selector_dubbed_anime_list_page_info: Selector,
     selector_dubbed_anime_list_anime_url: Selector,
+    selector_dubbed_anime_list_year: Selector,
+    selector_dubbed_anime_list_type: Selector,
+    selector_anime_canonical: Selector,
     selector_anime_dub_info: Selector,
     selector_anime_dub_status: Selector,
+    selector_anime_company_header: Selector,
+    selector_anime_company_name: Selector,
 }
 
-pub struct DubbedAnime {
-    pub total_pages: u64,
-    pub anisearch_urls: Box<[String]>,
-}
-
-#[derive(Debug, PartialEq, Eq)]
-pub enum DubStatus {
-    Complete,
-    Incomplete,
-    Upcoming,
-    NeverReleased,
-}
-
-impl AnisearchClient<'_> {
-    pub fn new(language: &Language) -> Self {
-        let anisearch_lang = language.get_anisearch_language();
-        let client = reqwest::blocking::Client::builder()
-            .user_agent(USER_AGENT)
-            .timeout(Duration::from_secs(20))
-            .connect_timeout(Duration::from_secs(20))
-            .build()
-            .unwrap();
+impl AnisearchClient {
+    pub fn new(language: &Language, base_url: &str, config: FetchConfig) -> Self {
+        let anisearch_lang = &language.code;
 
         Self {
-            client,
-            lang: anisearch_lang,
-            selector_dubbed_anime_list_page_info: scraper::Selector::parse(r#"div.pagenav-info"#).unwrap(),
+            fetcher: Fetcher::new(config),
+            language: language.clone(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            selector_dubbed_anime_list_page_info: scraper::Selector::parse(SELECTOR_PAGE_INFO).unwrap(),
             selector_dubbed_anime_list_anime_url: scraper::Selector::parse(r#"th > a[lang]"#).unwrap(),
+            selector_dubbed_anime_list_year: scraper::Selector::parse(r#"td.date"#).unwrap(),
+            selector_dubbed_anime_list_type: scraper::Selector::parse(r#"td.type"#).unwrap(),
+            selector_anime_canonical: scraper::Selector::parse(r#"link[rel="canonical"]"#).unwrap(),
             selector_anime_dub_info: scraper::Selector::parse(&format!(r#"div.title[lang="{anisearch_lang}"]"#))
                 .unwrap(),
             selector_anime_dub_status: scraper::Selector::parse(&format!(
                 r#"div.title[lang="{anisearch_lang}"] + div.status"#
             ))
             .unwrap(),
+            selector_anime_company_header: scraper::Selector::parse(r#"span.header"#).unwrap(),
+            selector_anime_company_name: scraper::Selector::parse(r#"a"#).unwrap(),
         }
     }
 }
 
-impl AnisearchClient<'_> {
-    fn get_page(&self, anisearch_url: &str) -> Result<scraper::Html, ()> {
-        fn wait_request_failed(message: &str, seconds: u64) {
-            for second in (1..=seconds).rev() {
-                log::info!("{message}, retrying in {second}...");
-                std::thread::sleep(Duration::from_secs(1));
-            }
-        }
-
-        const MAX_BACKOFF_SECONDS: u64 = 300; // max. 5 min backoff
-        let mut backoff_count: u64 = 0;
-
-        let body = loop {
-            let response = self.client.get(anisearch_url).send();
-            let body = match response {
-                Ok(res) => match res.status() {
-                    StatusCode::OK => match res.text() {
-                        Ok(text) => text,
-                        Err(err) => {
-                            log::error!("Failed to parse text for: {}. Error: {}", anisearch_url, err);
-                            return Err(());
-                        }
-                    },
-                    StatusCode::TOO_MANY_REQUESTS => {
-                        backoff_count += 1;
-                        wait_request_failed("Too many requests", (60 * backoff_count).min(MAX_BACKOFF_SECONDS));
-                        continue;
-                    }
-                    err if err.is_server_error() => {
-                        log::error!("aniSearch returned server error for: {}", anisearch_url);
-                        return Err(());
-                    }
-                    err => {
-                        log::error!("aniSearch returned error for: {}. Error: {}", anisearch_url, err);
-                        return Err(());
-                    }
-                },
-                Err(_) => {
-                    wait_request_failed("Request failed", (10 * backoff_count).min(MAX_BACKOFF_SECONDS));
-                    continue;
-                }
-            };
-
-            break body;
-        };
+impl AnisearchClient {
+    fn selector_anime_dub_info_text(&self) -> String {
+        format!(r#"div.title[lang="{}"]"#, self.language.code)
+    }
 
-        Ok(scraper::Html::parse_document(&body))
+    fn selector_anime_dub_status_text(&self) -> String {
+        format!(r#"div.title[lang="{}"] + div.status"#, self.language.code)
     }
 
-    pub fn get_dubbed_anime_list(&self, page: u64) -> Result<DubbedAnime, ()> {
-        let lang = self.lang;
+    pub fn get_dubbed_anime_list(&self, page: u64) -> Result<DubbedAnime> {
+        let lang = &self.language.code;
         let url = format!(
-            "https://www.anisearch.com/anime/index/page-{page}?synchro={lang}&sort=title&order=asc&view=2&limit=100"
+            "{}/anime/index/page-{page}?synchro={lang}&sort=title&order=asc&view=2&limit=100",
+            self.base_url
         );
-        let document = self.get_page(&url)?;
-        let total_pages = document
+        let document = self.fetcher.get_page(&url, PageClass::Listing)?;
+        let page_info = document
             .select(&self.selector_dubbed_anime_list_page_info)
             .next()
-            .unwrap()
+            .ok_or_else(|| Error::SelectorMissing {
+                url: url.clone(),
+                selector: SELECTOR_PAGE_INFO.to_string(),
+            })?
             .text()
-            .collect::<String>()
+            .collect::<String>();
+        let total_pages = page_info
             .trim()
             .chars()
             .rev()
@@ -124,7 +87,10 @@ impl AnisearchClient<'_> {
             .rev()
             .collect::<String>()
             .parse::<u64>()
-            .unwrap();
+            .map_err(|_| Error::UnexpectedLayout {
+                url: url.clone(),
+                reason: format!("no page count in: {}", page_info.trim()),
+            })?;
         let dubbed_elements = document
             .select(&self.selector_dubbed_anime_list_anime_url)
             .filter_map(|a_element| {
@@ -136,49 +102,176 @@ impl AnisearchClient<'_> {
                     }
                 };
 
-                Self::format_anisearch_url(href).ok()
+                // Links may be absolute, if the site is served from a different base URL
+                let href = href.strip_prefix(&self.base_url).unwrap_or(href);
+
+                let anisearch_url = Self::format_anisearch_url(href)
+                    .map_err(|err| log::error!("{}", err))
+                    .ok()?;
+                let row = a_element
+                    .ancestors()
+                    .filter_map(scraper::ElementRef::wrap)
+                    .find(|element| element.value().name() == "tr");
+                let cell_text = |selector: &Selector| {
+                    row.and_then(|row| row.select(selector).next())
+                        .map(|cell| cell.text().collect::<String>().trim().to_string())
+                        .filter(|text| !text.is_empty())
+                };
+                let year = cell_text(&self.selector_dubbed_anime_list_year).and_then(|year| year.parse().ok());
+                let r#type = cell_text(&self.selector_dubbed_anime_list_type);
+
+                Some(ListedAnime {
+                    url: anisearch_url,
+                    title: a_element.text().collect::<String>().trim().to_string(),
+                    synonyms: Vec::new(),
+                    year,
+                    r#type,
+                })
             })
             .collect();
 
         Ok(DubbedAnime {
             total_pages,
-            anisearch_urls: dubbed_elements,
+            entries: dubbed_elements,
         })
     }
 
-    pub fn get_dub_status(&self, anime_url: &str) -> Result<DubStatus, ()> {
-        let document = self.get_page(anime_url)?;
-        let status_text = document
+    /// Gets the dub status for an anime URL in the form of `https://anisearch.com/anime/<id>`.
+    #[cfg(test)]
+    pub fn get_dub_status(&self, anime_url: &str) -> Result<DubStatus> {
+        self.get_dub_info(anime_url).map(|dub_info| dub_info.status)
+    }
+
+    /// Gets the dub status, episode progress and release years for an anime URL.
+    pub fn get_dub_info(&self, anime_url: &str) -> Result<DubInfo> {
+        let page_url = self.page_url(anime_url)?;
+        let document = self.fetcher.get_page(&page_url, PageClass::Detail)?;
+        let selector_missing = |selector: String| Error::SelectorMissing {
+            url: page_url.clone(),
+            selector,
+        };
+        // The label follows the `Status:` header, e.g. `Completed`
+        let status_label = document
             .select(&self.selector_anime_dub_status)
             .next()
-            .ok_or(())?
-            .text()
-            .collect::<String>()
-            .to_ascii_lowercase();
+            .ok_or_else(|| selector_missing(self.selector_anime_dub_status_text()))?
+            .children()
+            .filter_map(|node| node.value().as_text())
+            .map(|text| &**text)
+            .collect::<String>();
+
+        let title_element = document
+            .select(&self.selector_anime_dub_info)
+            .next()
+            .ok_or_else(|| selector_missing(self.selector_anime_dub_info_text()))?;
 
-        Ok(if status_text.contains("completed") {
+        let status = if self.language.is_completed(&status_label) {
             DubStatus::Complete
-        } else if status_text.contains("upcoming") {
+        } else if self.language.is_upcoming(&status_label) {
             DubStatus::Upcoming
         } else {
-            let never_released = document
-                .select(&self.selector_anime_dub_info)
-                .next()
-                .ok_or(())?
-                .text()
-                .collect::<String>()
-                .to_ascii_lowercase()
-                .contains("never released");
+            let title_text = title_element.text().collect::<String>();
 
-            if never_released {
+            if self.language.is_never_released(&title_text) {
                 DubStatus::NeverReleased
             } else {
                 DubStatus::Incomplete
             }
-        })
+        };
+
+        // The block of the language ends with the title of the next language
+        let block: Vec<scraper::ElementRef> = title_element
+            .next_siblings()
+            .filter_map(scraper::ElementRef::wrap)
+            .take_while(|element| {
+                !element
+                    .value()
+                    .has_class("title", CaseSensitivity::AsciiCaseInsensitive)
+            })
+            .collect();
+        let block_text = |class: &str| {
+            block
+                .iter()
+                .find(|element| element.value().has_class(class, CaseSensitivity::AsciiCaseInsensitive))
+                .map(|element| element.text().collect::<String>())
+        };
+
+        let mut dub_info = DubInfo {
+            status,
+            episodes: block_text("type").and_then(|text| parse_dub_episodes(&text)),
+            years: block_text("released").and_then(|text| parse_release_years(&text)),
+            licensors: Vec::new(),
+            publishers: Vec::new(),
+            dub_studios: Vec::new(),
+            moved_to: None,
+            content_hash: Some(state::content_hash(
+                &std::iter::once(title_element)
+                    .chain(block.iter().copied())
+                    .map(|element| element.html())
+                    .collect::<String>(),
+            )),
+        };
+
+        // aniSearch redirects merged entries to the remaining one
+        if let Some(canonical) = document
+            .select(&self.selector_anime_canonical)
+            .next()
+            .and_then(|link| link.value().attr("href"))
+        {
+            // The canonical link points to the domain of the language, e.g. anisearch.de
+            let path = canonical
+                .find("/anime/")
+                .map_or(canonical, |index| &canonical[index + 1..]);
+
+            match Self::format_anisearch_url(path) {
+                Ok(canonical_url) if canonical_url != anime_url => dub_info.moved_to = Some(canonical_url),
+                Ok(_) => {}
+                Err(err) => log::debug!("Ignoring canonical link on {}: {}", page_url, err),
+            }
+        }
+
+        for company_element in block.iter().filter(|element| {
+            element
+                .value()
+                .has_class("company", CaseSensitivity::AsciiCaseInsensitive)
+        }) {
+            let header = company_element
+                .select(&self.selector_anime_company_header)
+                .next()
+                .map(|header| header.text().collect::<String>())
+                .unwrap_or_default();
+            let names = company_element
+                .select(&self.selector_anime_company_name)
+                .map(|name| name.text().collect::<String>().trim().to_string())
+                .filter(|name| !name.is_empty());
+
+            let header = header.to_lowercase();
+            let companies = if header.contains("licens") || header.contains("lizenz") {
+                &mut dub_info.licensors
+            } else if header.contains("publisher") {
+                &mut dub_info.publishers
+            } else if header.contains("studio") {
+                &mut dub_info.dub_studios
+            } else {
+                log::debug!("Ignoring unknown company role `{}` on: {}", header.trim(), page_url);
+                continue;
+            };
+
+            companies.extend(names);
+        }
+
+        Ok(dub_info)
+    }
+
+    /// Turns an anime URL as returned by [`Self::format_anisearch_url`] into the URL of the page to fetch.
+    fn page_url(&self, anime_url: &str) -> Result<String> {
+        match anime_url.strip_prefix(ANIME_URL_PREFIX) {
+            Some(id) => Ok(format!("{}/anime/{}", self.base_url, id)),
+            None => Err(Error::InvalidUrl(anime_url.to_string())),
+        }
     }
 
-    fn format_anisearch_url(url: &str) -> Result<String, ()> {
+    fn format_anisearch_url(url: &str) -> Result<String> {
         // anime/1540,alps-monogatari-watashi-no-annette
         // -> https://anisearch.com/anime/1540
         let url = url.to_lowercase();
@@ -194,7 +287,7 @@ impl AnisearchClient<'_> {
             format!("https://anisearch.com/{}", url)
         };
 
-        let anime_prefix = "https://anisearch.com/anime/";
+        let anime_prefix = ANIME_URL_PREFIX;
 
         if let Some(id_and_name) = url.strip_prefix(anime_prefix) {
             let id = id_and_name
@@ -203,18 +296,88 @@ impl AnisearchClient<'_> {
                 .collect::<String>();
             Ok(format!("{}{}", anime_prefix, id))
         } else {
-            log::error!("Could not format aniSearch url: {}", url);
-            Err(())
+            Err(Error::InvalidUrl(url))
         }
     }
 }
 
+impl DubSource for AnisearchClient {
+    fn name(&self) -> &'static str {
+        "aniSearch"
+    }
+
+    fn database_prefix(&self) -> Option<&'static str> {
+        Some(ANIME_URL_PREFIX)
+    }
+
+    fn max_in_flight(&self) -> usize {
+        self.fetcher.max_in_flight()
+    }
+
+    fn get_dubbed_anime_list(&self, page: u64) -> Result<DubbedAnime> {
+        AnisearchClient::get_dubbed_anime_list(self, page)
+    }
+
+    fn get_dub_info(&self, url: &str) -> Result<DubInfo> {
+        AnisearchClient::get_dub_info(self, url)
+    }
+}
+
+/// Parses the dubbed episodes from e.g. `TV-Series, 12/24` or `Movie, 1`.
+fn parse_dub_episodes(text: &str) -> Option<DubEpisodes> {
+    let episodes = text
+        .split_once(',')
+        .map(|(_, episodes)| episodes)
+        .unwrap_or(text)
+        .trim();
+    let episodes = episodes.split_whitespace().next()?;
+
+    match episodes.split_once('/') {
+        Some((dubbed, total)) => Some(DubEpisodes {
+            dubbed: dubbed.parse().ok()?,
+            total: total.parse().ok(),
+        }),
+        None => Some(DubEpisodes {
+            dubbed: episodes.parse().ok()?,
+            total: None,
+        }),
+    }
+}
+
+/// Parses the first and last year from e.g. `2001 ‑ 2023` or `26.08.2016 ‑ ?`.
+fn parse_release_years(text: &str) -> Option<(u32, Option<u32>)> {
+    let mut years = text
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|number| number.len() == 4)
+        .filter_map(|number| number.parse::<u32>().ok());
+    let first_year = years.next()?;
+
+    Some((first_year, years.next_back()))
+}
+
 #[cfg(test)]
 mod tests {
-    use std::time::Duration;
+    use std::path::Path;
+
+    use super::{AnisearchClient, DEFAULT_BASE_URL};
+    use crate::archive::{Archive, ArchiveMode};
+    use crate::error::Error;
+    use crate::fetch::FetchConfig;
+    use crate::language::LanguageTable;
+    use crate::source::{DubEpisodes, DubInfo, DubStatus, ListedAnime};
 
-    use super::AnisearchClient;
-    use crate::{anisearch::DubStatus, cli::Language};
+    fn replay_client() -> AnisearchClient {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/replay");
+
+        AnisearchClient::new(
+            LanguageTable::default().find("de").unwrap(),
+            DEFAULT_BASE_URL,
+            FetchConfig {
+                archive: Some(ArchiveMode::Replay(Archive::new(&fixtures))),
+                ..Default::default()
+            },
+        )
+    }
 
     #[test]
     fn test_format_anisearch_url() {
@@ -225,8 +388,9 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::redundant_pattern_matching)]
     fn test_get_dub_status() {
-        let anisearch_client = AnisearchClient::new(&Language::German);
+        let anisearch_client = replay_client();
 
         assert_eq!(
             anisearch_client.get_dub_status("https://anisearch.com/anime/15141"),
@@ -246,6 +410,73 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_get_dub_info() {
+        let anisearch_client = replay_client();
+
+        assert_eq!(
+            anisearch_client
+                .get_dub_info("https://anisearch.com/anime/14")
+                // The hash is tested in the state module
+                .map(|dub_info| DubInfo {
+                    content_hash: None,
+                    ..dub_info
+                }),
+            Ok(DubInfo {
+                status: DubStatus::Incomplete,
+                episodes: Some(DubEpisodes {
+                    dubbed: 650,
+                    total: Some(1100)
+                }),
+                years: Some((2001, Some(2023))),
+                licensors: vec![],
+                publishers: vec![],
+                dub_studios: vec![],
+                moved_to: None,
+                content_hash: None,
+            })
+        );
+        assert_eq!(
+            anisearch_client
+                .get_dub_info("https://anisearch.com/anime/15141")
+                // The hash is tested in the state module
+                .map(|dub_info| DubInfo {
+                    content_hash: None,
+                    ..dub_info
+                }),
+            Ok(DubInfo {
+                status: DubStatus::Complete,
+                episodes: Some(DubEpisodes { dubbed: 1, total: None }),
+                years: Some((2017, None)),
+                licensors: vec!["Universum Anime".to_string()],
+                publishers: vec!["Universum Anime".to_string()],
+                dub_studios: vec!["Interopa Film".to_string()],
+                moved_to: None,
+                content_hash: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let anisearch_client = replay_client();
+
+        assert!(matches!(
+            anisearch_client.get_dub_status("https://anisearch.com/anime/18285"),
+            Err(Error::SelectorMissing { .. })
+        ));
+        assert_eq!(
+            anisearch_client.get_dub_status("https://anisearch.com/anime/999999"),
+            Err(Error::ReplayMiss {
+                url: "https://www.anisearch.com/anime/999999".to_string()
+            })
+        );
+        assert_eq!(
+            AnisearchClient::format_anisearch_url("https://anisearch.com/character/1"),
+            Err(Error::InvalidUrl("https://anisearch.com/character/1".to_string()))
+        );
+    }
+
     #[test]
     fn test_never_released() {
         let never_released_anisearch_ids = [
@@ -253,7 +484,7 @@ mod tests {
             4004, 3735, 4421, 6655, 6671, 7268, 10083, 11787, 12916, 14791,
         ];
 
-        let anisearch_client = AnisearchClient::new(&Language::German);
+        let anisearch_client = replay_client();
 
         for id in never_released_anisearch_ids {
             assert_eq!(
@@ -261,7 +492,39 @@ mod tests {
                 Ok(DubStatus::NeverReleased),
                 "failed for id {id}"
             );
-            std::thread::sleep(Duration::from_millis(500));
         }
     }
+
+    #[test]
+    fn test_get_dubbed_anime_list() {
+        let dubbed_anime = replay_client().get_dubbed_anime_list(1).unwrap();
+
+        assert_eq!(dubbed_anime.total_pages, 27);
+        assert_eq!(
+            dubbed_anime.entries.as_ref(),
+            [
+                ListedAnime {
+                    url: "https://anisearch.com/anime/1540".to_string(),
+                    title: "Alps Monogatari: Watashi no Annette".to_string(),
+                    synonyms: Vec::new(),
+                    year: Some(1983),
+                    r#type: Some("TV-Series".to_string()),
+                },
+                ListedAnime {
+                    url: "https://anisearch.com/anime/14".to_string(),
+                    title: "Meitantei Conan".to_string(),
+                    synonyms: Vec::new(),
+                    year: Some(1996),
+                    r#type: Some("TV-Series".to_string()),
+                },
+                ListedAnime {
+                    url: "https://anisearch.com/anime/15141".to_string(),
+                    title: "Kimi no Na wa.".to_string(),
+                    synonyms: Vec::new(),
+                    year: Some(2016),
+                    r#type: Some("Movie".to_string()),
+                },
+            ]
+        );
+    }
 }
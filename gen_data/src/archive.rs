@@ -0,0 +1,113 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::output;
+
+/// Whether fetched pages are written to or served from an archive.
+pub enum ArchiveMode {
+    Record(Archive),
+    Replay(Archive),
+}
+
+/// Directory of archived responses, with a metadata file and a body file per URL.
+///
+/// The file names are derived from the URL, e.g. `anisearch.com_anime_14.json`
+/// and `anisearch.com_anime_14.html` for `https://www.anisearch.com/anime/14`.
+pub struct Archive {
+    dir: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ArchiveMetadata {
+    url: String,
+    status: u16,
+    headers: BTreeMap<String, String>,
+    timestamp: String,
+    body_file: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ArchivedResponse {
+    pub status: u16,
+    pub headers: BTreeMap<String, String>,
+    pub body: String,
+}
+
+impl Archive {
+    pub fn new(dir: &Path) -> Self {
+        Self { dir: dir.to_path_buf() }
+    }
+
+    pub fn record(&self, url: &str, response: &ArchivedResponse) {
+        let file_stem = file_stem(url);
+        let body_file = format!("{file_stem}.html");
+        let metadata = ArchiveMetadata {
+            url: url.to_string(),
+            status: response.status,
+            headers: response.headers.clone(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            body_file: body_file.clone(),
+        };
+
+        // The metadata is written last, so that it never refers to a missing or partially written body
+        let result = output::write_atomically(&self.dir.join(&body_file), response.body.as_bytes()).and_then(|_| {
+            let content = serde_json::to_string_pretty(&metadata).expect("failed to serialize archive metadata");
+            output::write_atomically(&self.dir.join(format!("{file_stem}.json")), content.as_bytes())
+        });
+
+        if let Err(err) = result {
+            log::error!("Failed to archive response for: {}. Error: {}", url, err);
+        }
+    }
+
+    pub fn replay(&self, url: &str) -> Option<ArchivedResponse> {
+        let content = std::fs::read_to_string(self.dir.join(format!("{}.json", file_stem(url)))).ok()?;
+        let metadata: ArchiveMetadata = serde_json::from_str(&content).ok()?;
+        let body = std::fs::read_to_string(self.dir.join(&metadata.body_file)).ok()?;
+
+        Some(ArchivedResponse {
+            status: metadata.status,
+            headers: metadata.headers,
+            body,
+        })
+    }
+}
+
+fn file_stem(url: &str) -> String {
+    let url = url.to_lowercase();
+    let url = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .unwrap_or(&url);
+    let url = url.strip_prefix("www.").unwrap_or(url);
+
+    url.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::file_stem;
+
+    #[test]
+    fn test_file_stem() {
+        assert_eq!(
+            file_stem("https://www.anisearch.com/anime/14"),
+            "anisearch.com_anime_14"
+        );
+        assert_eq!(
+            file_stem("https://www.anisearch.com/anime/index/page-2?synchro=de&limit=100"),
+            "anisearch.com_anime_index_page-2_synchro_de_limit_100"
+        );
+    }
+}
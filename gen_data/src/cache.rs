@@ -0,0 +1,122 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::output;
+
+/// Kind of aniSearch page, which decides how long a cached response stays fresh.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageClass {
+    Listing,
+    Detail,
+}
+
+/// Persistent HTTP response cache with one file per URL.
+pub struct ResponseCache {
+    dir: PathBuf,
+    listing_max_age: Duration,
+    detail_max_age: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheEntry {
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// Seconds since the Unix epoch
+    pub fetched_at: u64,
+    pub body: String,
+}
+
+impl ResponseCache {
+    pub fn new(dir: &Path, listing_max_age: Duration, detail_max_age: Duration) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            listing_max_age,
+            detail_max_age,
+        }
+    }
+
+    pub fn load(&self, url: &str) -> Option<CacheEntry> {
+        let content = std::fs::read_to_string(self.entry_path(url)).ok()?;
+
+        match serde_json::from_str::<CacheEntry>(&content) {
+            Ok(entry) if entry.url == url => Some(entry),
+            Ok(_) => None,
+            Err(err) => {
+                log::warn!("Ignoring corrupt cache entry for: {}. Error: {}", url, err);
+                None
+            }
+        }
+    }
+
+    pub fn store(&self, entry: &CacheEntry) {
+        let content = serde_json::to_string(entry).expect("failed to serialize cache entry");
+
+        if let Err(err) = output::write_atomically(&self.entry_path(&entry.url), content.as_bytes()) {
+            log::warn!("Failed to write cache entry for: {}. Error: {}", entry.url, err);
+        }
+    }
+
+    /// Returns true, if the entry may be used without revalidating it first.
+    pub fn is_fresh(&self, entry: &CacheEntry, page_class: PageClass) -> bool {
+        let max_age = match page_class {
+            PageClass::Listing => self.listing_max_age,
+            PageClass::Detail => self.detail_max_age,
+        };
+
+        now_secs().saturating_sub(entry.fetched_at) < max_age.as_secs()
+    }
+
+    fn entry_path(&self, url: &str) -> PathBuf {
+        self.dir.join(format!("{:016x}.json", fnv1a_hash(url.as_bytes())))
+    }
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+/// Stable 64-bit FNV-1a hash, used for file names and content hashes.
+pub fn fnv1a_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{now_secs, CacheEntry, PageClass, ResponseCache};
+
+    #[test]
+    fn test_store_load_and_freshness() {
+        let dir = std::env::temp_dir().join(format!("mal_gerdubs_cache_test_{}", std::process::id()));
+        let cache = ResponseCache::new(&dir, Duration::ZERO, Duration::from_secs(3600));
+        let url = "https://www.anisearch.com/anime/14";
+
+        assert!(cache.load(url).is_none());
+
+        cache.store(&CacheEntry {
+            url: url.to_string(),
+            etag: Some("\"abc\"".to_string()),
+            last_modified: None,
+            fetched_at: now_secs() - 60,
+            body: "<html></html>".to_string(),
+        });
+
+        let entry = cache.load(url).unwrap();
+        assert_eq!(entry.etag.as_deref(), Some("\"abc\""));
+        assert!(!cache.is_fresh(&entry, PageClass::Listing));
+        assert!(cache.is_fresh(&entry, PageClass::Detail));
+        assert!(cache.load("https://www.anisearch.com/anime/15").is_none());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
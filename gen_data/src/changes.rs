@@ -0,0 +1,287 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+
+use serde::Serialize;
+
+use crate::output::Output;
+
+/// Differences between the previous and the new dub data.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Changes {
+    pub newly_dubbed: Vec<ChangedTitle>,
+    pub no_longer_dubbed: Vec<ChangedTitle>,
+    /// Dubbed titles, which have been incomplete before
+    pub now_complete: Vec<ChangedTitle>,
+    /// Dubbed titles, which have been complete before
+    pub now_incomplete: Vec<ChangedTitle>,
+    /// Titles, whose dub has newly been found to have never been released
+    pub newly_never_released: Vec<ChangedTitle>,
+    /// MyAnimeList ids of the dubbed titles, whose dub progress has changed
+    pub progress_changed: Vec<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangedTitle {
+    pub mal_id: u64,
+    /// Title in the offline database, if the MyAnimeList id is still known
+    pub title: Option<String>,
+}
+
+impl Changes {
+    /// Compares the dub data. `titles` are the titles of the offline database.
+    pub fn new(previous: &Output, current: &Output, titles: &HashMap<u64, &str>) -> Self {
+        let previous_dubbed: BTreeSet<u64> = previous.dubbed.iter().copied().collect();
+        let previous_incomplete: BTreeSet<u64> = previous.incomplete.iter().copied().collect();
+        let current_dubbed: BTreeSet<u64> = current.dubbed.iter().copied().collect();
+        let current_incomplete: BTreeSet<u64> = current.incomplete.iter().copied().collect();
+        let previous_never_released: BTreeSet<u64> = previous.never_released.iter().copied().collect();
+        let current_never_released: BTreeSet<u64> = current.never_released.iter().copied().collect();
+
+        let changed_titles = |mal_ids: &mut dyn Iterator<Item = &u64>| {
+            mal_ids
+                .map(|&mal_id| ChangedTitle {
+                    mal_id,
+                    title: titles.get(&mal_id).map(|title| title.to_string()),
+                })
+                .collect::<Vec<_>>()
+        };
+        let still_dubbed: BTreeSet<u64> = previous_dubbed.intersection(&current_dubbed).copied().collect();
+
+        Self {
+            newly_dubbed: changed_titles(&mut current_dubbed.difference(&previous_dubbed)),
+            no_longer_dubbed: changed_titles(&mut previous_dubbed.difference(&current_dubbed)),
+            now_complete: changed_titles(
+                &mut still_dubbed
+                    .iter()
+                    .filter(|mal_id| previous_incomplete.contains(mal_id) && !current_incomplete.contains(mal_id)),
+            ),
+            now_incomplete: changed_titles(
+                &mut still_dubbed
+                    .iter()
+                    .filter(|mal_id| !previous_incomplete.contains(mal_id) && current_incomplete.contains(mal_id)),
+            ),
+            newly_never_released: changed_titles(&mut current_never_released.difference(&previous_never_released)),
+            progress_changed: still_dubbed
+                .iter()
+                .copied()
+                .filter(|mal_id| previous.progress.get(mal_id) != current.progress.get(mal_id))
+                .collect(),
+        }
+    }
+
+    /// One line summary, e.g. `2 newly dubbed, 1 no longer dubbed`.
+    pub fn summary(&self) -> String {
+        let counts = [
+            (self.newly_dubbed.len(), "newly dubbed"),
+            (self.no_longer_dubbed.len(), "no longer dubbed"),
+            (self.now_complete.len(), "now complete"),
+            (self.now_incomplete.len(), "now incomplete"),
+            (self.newly_never_released.len(), "never released"),
+            (self.progress_changed.len(), "with changed progress"),
+        ];
+        let parts: Vec<String> = counts
+            .iter()
+            .filter(|(count, _)| *count > 0)
+            .map(|(count, description)| format!("{count} {description}"))
+            .collect();
+
+        if parts.is_empty() {
+            "no changes".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+
+    /// Formats the changes as Markdown, whose first line can be used as the subject of a commit message.
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = format!("Update dub data: {}\n", self.summary());
+
+        for (heading, changed_titles) in [
+            ("Newly dubbed", &self.newly_dubbed),
+            ("No longer dubbed", &self.no_longer_dubbed),
+            ("Now complete", &self.now_complete),
+            ("Now incomplete", &self.now_incomplete),
+            ("Never released", &self.newly_never_released),
+        ] {
+            if changed_titles.is_empty() {
+                continue;
+            }
+
+            write!(markdown, "\n## {heading}\n\n").unwrap();
+
+            for changed_title in changed_titles {
+                writeln!(
+                    markdown,
+                    "- {} (https://myanimelist.net/anime/{})",
+                    changed_title.title.as_deref().unwrap_or("Unknown title"),
+                    changed_title.mal_id
+                )
+                .unwrap();
+            }
+        }
+
+        markdown
+    }
+}
+
+/// Limits of the changes, beyond which a source is more likely broken than the dub data changed.
+#[derive(Debug, Clone, Copy)]
+pub struct Thresholds {
+    /// Maximum fraction, by which the number of dubbed titles may shrink
+    pub max_shrink: f64,
+    pub max_removals: usize,
+    pub min_dubbed: usize,
+}
+
+impl Thresholds {
+    /// Returns a description of every threshold, which the new dub data violates.
+    pub fn violations(&self, previous: &Output, current: &Output, changes: &Changes) -> Vec<String> {
+        let mut violations = Vec::new();
+        let (previous_dubbed, current_dubbed) = (previous.dubbed.len(), current.dubbed.len());
+
+        if current_dubbed < previous_dubbed {
+            let shrink = (previous_dubbed - current_dubbed) as f64 / previous_dubbed as f64;
+
+            if shrink > self.max_shrink {
+                violations.push(format!(
+                    "dubbed titles shrank by {:.1}% from {} to {}, but at most {:.1}% are allowed",
+                    shrink * 100.0,
+                    previous_dubbed,
+                    current_dubbed,
+                    self.max_shrink * 100.0
+                ));
+            }
+        }
+
+        if changes.no_longer_dubbed.len() > self.max_removals {
+            violations.push(format!(
+                "{} titles are no longer dubbed, but at most {} are allowed",
+                changes.no_longer_dubbed.len(),
+                self.max_removals
+            ));
+        }
+
+        if current_dubbed < self.min_dubbed {
+            violations.push(format!(
+                "only {} titles are dubbed, but at least {} are required",
+                current_dubbed, self.min_dubbed
+            ));
+        }
+
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use super::{ChangedTitle, Changes, Thresholds};
+    use crate::output::Output;
+
+    #[test]
+    fn test_changes() {
+        let previous = Output {
+            dubbed: vec![1, 2, 3, 4, 5],
+            incomplete: vec![2, 4],
+            never_released: vec![7],
+            progress: BTreeMap::from([(2, 50), (4, 10)]),
+            ..Default::default()
+        };
+        let current = Output {
+            dubbed: vec![1, 2, 3, 6],
+            incomplete: vec![2, 3],
+            never_released: vec![4, 7],
+            progress: BTreeMap::from([(2, 75), (3, 90)]),
+            ..Default::default()
+        };
+        let titles = HashMap::from([(1, "Ashita no Joe"), (4, "Death Note"), (6, "Fullmetal Alchemist")]);
+
+        let changes = Changes::new(&previous, &current, &titles);
+        assert_eq!(
+            changes.newly_dubbed,
+            [ChangedTitle {
+                mal_id: 6,
+                title: Some("Fullmetal Alchemist".to_string())
+            }]
+        );
+        assert_eq!(
+            changes
+                .no_longer_dubbed
+                .iter()
+                .map(|title| title.mal_id)
+                .collect::<Vec<_>>(),
+            [4, 5]
+        );
+        assert_eq!(changes.no_longer_dubbed[1].title, None);
+        assert!(changes.now_complete.is_empty());
+        assert_eq!(
+            changes
+                .now_incomplete
+                .iter()
+                .map(|title| title.mal_id)
+                .collect::<Vec<_>>(),
+            [3]
+        );
+        assert_eq!(
+            changes
+                .newly_never_released
+                .iter()
+                .map(|title| title.mal_id)
+                .collect::<Vec<_>>(),
+            [4]
+        );
+        assert_eq!(changes.progress_changed, [2, 3]);
+        assert_eq!(
+            changes.summary(),
+            "1 newly dubbed, 2 no longer dubbed, 1 now incomplete, 1 never released, 2 with changed progress"
+        );
+
+        let markdown = changes.to_markdown();
+        assert!(markdown.starts_with("Update dub data: 1 newly dubbed, "));
+        assert!(markdown.contains("## No longer dubbed\n\n- Death Note (https://myanimelist.net/anime/4)\n"));
+        assert!(markdown.contains("- Unknown title (https://myanimelist.net/anime/5)\n"));
+
+        assert_eq!(Changes::new(&current, &current, &titles).summary(), "no changes");
+    }
+
+    #[test]
+    fn test_thresholds() {
+        let previous = Output {
+            dubbed: (1..=10).collect(),
+            ..Default::default()
+        };
+        let current = Output {
+            dubbed: (1..=8).collect(),
+            ..Default::default()
+        };
+        let changes = Changes::new(&previous, &current, &HashMap::new());
+        let thresholds = Thresholds {
+            max_shrink: 0.2,
+            max_removals: 2,
+            min_dubbed: 8,
+        };
+
+        assert!(thresholds.violations(&previous, &current, &changes).is_empty());
+        // Growing is always fine
+        assert!(thresholds
+            .violations(&current, &previous, &Changes::default())
+            .is_empty());
+
+        let thresholds = Thresholds {
+            max_shrink: 0.1,
+            max_removals: 1,
+            min_dubbed: 9,
+        };
+        assert_eq!(
+            thresholds.violations(&previous, &current, &changes),
+            [
+                "dubbed titles shrank by 20.0% from 10 to 8, but at most 10.0% are allowed",
+                "2 titles are no longer dubbed, but at most 1 are allowed",
+                "only 8 titles are dubbed, but at least 9 are required",
+            ]
+        );
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::output;
+use crate::source::ListedAnime;
+use crate::state::EntryState;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Stops the run after the requests in flight on SIGINT or SIGTERM, so that a checkpoint can be written.
+///
+/// A second signal exits immediately.
+pub fn handle_signals() {
+    let result = ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            std::process::exit(130);
+        }
+
+        log::warn!("Interrupted, writing a checkpoint after the requests in flight...");
+    });
+
+    if let Err(err) = result {
+        log::warn!("Could not handle signals: {}", err);
+    }
+}
+
+/// Whether the run has been interrupted by a signal.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Sleeps for the given duration, but stops early with [`Error::Interrupted`] once the run has been interrupted.
+pub fn sleep(duration: Duration) -> Result<()> {
+    const SLICE: Duration = Duration::from_millis(100);
+    let deadline = Instant::now() + duration;
+
+    loop {
+        if interrupted() {
+            return Err(Error::Interrupted);
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+
+        if remaining.is_zero() {
+            return Ok(());
+        }
+
+        std::thread::sleep(remaining.min(SLICE));
+    }
+}
+
+/// Progress of an unfinished run, keyed by the name of the source.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub sources: BTreeMap<String, SourceCheckpoint>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceCheckpoint {
+    pub total_pages: Option<u64>,
+    pub visited_pages: BTreeSet<u64>,
+    /// Dubbed anime of the visited pages
+    pub listed_anime: Vec<ListedAnime>,
+    /// Dub infos of the entries, which have been checked successfully, and when they have been checked
+    pub entries: BTreeMap<String, EntryState>,
+}
+
+/// Collects the progress of a run and writes it to a file every few updates.
+pub struct Checkpointer {
+    path: PathBuf,
+    checkpoint: Mutex<Checkpoint>,
+    /// Number of updates since the checkpoint has been written
+    pending: AtomicUsize,
+}
+
+impl Checkpointer {
+    const UPDATES_PER_WRITE: usize = 25;
+
+    /// Starts with the checkpoint at `path`, if `resume` is set, or with an empty one otherwise.
+    pub fn new(path: PathBuf, resume: bool) -> Result<Self> {
+        let checkpoint = match std::fs::read_to_string(&path) {
+            Ok(text) if resume => {
+                log::info!("Resuming from checkpoint: {}", path.display());
+                serde_json::from_str(&text).map_err(|err| Error::InvalidFile {
+                    path: path.display().to_string(),
+                    message: err.to_string(),
+                })?
+            }
+            Err(err) if resume && err.kind() == std::io::ErrorKind::NotFound => {
+                log::warn!("No checkpoint to resume from, starting over: {}", path.display());
+                Checkpoint::default()
+            }
+            Err(err) if resume => {
+                return Err(Error::Io {
+                    path: path.display().to_string(),
+                    message: err.to_string(),
+                })
+            }
+            _ => Checkpoint::default(),
+        };
+
+        Ok(Self {
+            path,
+            checkpoint: Mutex::new(checkpoint),
+            pending: AtomicUsize::new(0),
+        })
+    }
+
+    /// Returns the progress of a source, which is empty if it has not been started yet.
+    pub fn source(&self, source_name: &str) -> SourceCheckpoint {
+        let checkpoint = self.checkpoint.lock().unwrap();
+        checkpoint.sources.get(source_name).cloned().unwrap_or_default()
+    }
+
+    pub fn record_page(&self, source_name: &str, page: u64, total_pages: u64, entries: &[ListedAnime]) {
+        self.update(source_name, |source| {
+            source.total_pages = Some(total_pages);
+
+            if source.visited_pages.insert(page) {
+                source.listed_anime.extend_from_slice(entries);
+            }
+        });
+    }
+
+    pub fn record_entry(&self, source_name: &str, url: &str, entry_state: &EntryState) {
+        self.update(source_name, |source| {
+            source.entries.insert(url.to_string(), entry_state.clone());
+        });
+    }
+
+    fn update(&self, source_name: &str, f: impl FnOnce(&mut SourceCheckpoint)) {
+        f(self
+            .checkpoint
+            .lock()
+            .unwrap()
+            .sources
+            .entry(source_name.to_string())
+            .or_default());
+
+        if self.pending.fetch_add(1, Ordering::SeqCst) + 1 >= Self::UPDATES_PER_WRITE {
+            if let Err(err) = self.write() {
+                log::warn!("Failed to write checkpoint: {}", err);
+            }
+        }
+    }
+
+    /// Writes the checkpoint, e.g. before the run is aborted.
+    pub fn write(&self) -> Result<()> {
+        let checkpoint = self.checkpoint.lock().unwrap();
+        self.pending.store(0, Ordering::SeqCst);
+        output::write_checkpoint(&self.path, &checkpoint)
+    }
+
+    /// Removes the checkpoint after the run has finished.
+    pub fn remove(&self) {
+        if let Err(err) = std::fs::remove_file(&self.path) {
+            if err.kind() != std::io::ErrorKind::NotFound {
+                log::warn!("Failed to remove checkpoint {}: {}", self.path.display(), err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::Checkpointer;
+    use crate::source::{DubInfo, DubStatus, ListedAnime};
+    use crate::state::EntryState;
+
+    #[test]
+    fn test_resume() {
+        let path = std::env::temp_dir().join(format!("mal_gerdubs_checkpoint_{}.json", std::process::id()));
+        let listed = ListedAnime {
+            url: "https://anisearch.com/anime/14".to_string(),
+            title: "Meitantei Conan".to_string(),
+            synonyms: Vec::new(),
+            year: Some(1996),
+            r#type: None,
+        };
+
+        let checkpointer = Checkpointer::new(path.clone(), false).unwrap();
+        checkpointer.record_page("aniSearch", 2, 3, std::slice::from_ref(&listed));
+        // Pages are only recorded once
+        checkpointer.record_page("aniSearch", 2, 3, std::slice::from_ref(&listed));
+        let checked = Utc.with_ymd_and_hms(2023, 10, 1, 12, 0, 0).unwrap();
+        let entry_state = EntryState::new(
+            DubInfo {
+                status: DubStatus::Complete,
+                episodes: None,
+                years: None,
+                licensors: Vec::new(),
+                publishers: Vec::new(),
+                dub_studios: Vec::new(),
+                moved_to: None,
+                content_hash: None,
+            },
+            checked,
+        );
+        checkpointer.record_entry("aniSearch", &listed.url, &entry_state);
+        checkpointer.write().unwrap();
+
+        let resumed = Checkpointer::new(path.clone(), true).unwrap().source("aniSearch");
+        assert_eq!(resumed.total_pages, Some(3));
+        assert_eq!(resumed.visited_pages.into_iter().collect::<Vec<_>>(), [2]);
+        // Resumed entries keep the time of their check
+        assert_eq!(resumed.entries[&listed.url], entry_state);
+        assert_eq!(resumed.listed_anime, [listed]);
+
+        // Without resuming, the checkpoint is ignored
+        assert!(Checkpointer::new(path.clone(), false)
+            .unwrap()
+            .source("aniSearch")
+            .visited_pages
+            .is_empty());
+
+        checkpointer.remove();
+        assert!(!path.exists());
+    }
+}
@@ -1,31 +1,268 @@
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::path::PathBuf;
+
 use clap::{Parser, ValueEnum};
 
+use crate::anisearch::DEFAULT_BASE_URL;
+use crate::fetch::DEFAULT_USER_AGENT;
+use crate::source::DubStatus;
+use crate::synchronkartei::DEFAULT_SYNCHRONKARTEI_URL;
+
+const EXIT_CODES: &str = "Exit codes:
+  0  Success
+  1  The dub data would change, with --check
+  2  Invalid arguments
+  3  A source could not be reached or returned an error
+  4  Pages of a source are not in the expected format
+  5  A local file could not be read or written, or is invalid
+  6  The replay archive is missing a page
+  7  robots.txt does not allow fetching a required page
+  8  A source seems to be down, too many requests in a row failed
+  9  Interrupted by a signal, continue with --resume
+ 10  The dub data failed a sanity check, write it anyway with --force";
+
 #[derive(Parser, Debug)]
-#[command(version)]
+#[command(version, after_help = EXIT_CODES)]
 /// Generate complete and incomplete dub data with their respective MAL ids
 pub(crate) struct Args {
-    /// Search for dubs in this language
-    #[arg(value_enum, short, long, ignore_case = true, default_value_t = Language::German)]
-    pub(crate) language: Language,
+    /// Search for dubs in these languages, given by aniSearch language code or name.
+    /// With more than one, the language code is added to every output path, e.g. dubInfo.de.json
+    #[arg(short, long, value_delimiter = ',', default_value = "de")]
+    pub(crate) language: Vec<String>,
+
+    /// TOML file with the known languages and the keywords of their dub statuses, instead of the built-in one
+    #[arg(long, value_name = "PATH")]
+    pub(crate) language_table: Option<PathBuf>,
+
+    /// Keep the dub info of every entry in this file, so that the next run only checks entries, which are new,
+    /// not complete or have not been checked for a while
+    #[arg(long, value_name = "PATH")]
+    pub(crate) state: Option<PathBuf>,
+
+    /// File, to which the progress is written regularly and when the run is aborted or interrupted
+    #[arg(long, value_name = "PATH", default_value = ".cache/checkpoint.json")]
+    pub(crate) checkpoint: PathBuf,
+
+    /// Continue from the checkpoint of an aborted or interrupted run
+    #[arg(long)]
+    pub(crate) resume: bool,
+
+    /// Days after which a complete dub is checked again
+    #[arg(long, value_name = "DAYS", default_value_t = 30)]
+    pub(crate) recheck_after: u32,
+
+    /// Check every entry, even if the state file has a recent dub info for it
+    #[arg(long)]
+    pub(crate) full: bool,
+
+    /// Sources of dub information, ordered by descending precedence
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "anisearch")]
+    pub(crate) sources: Vec<Source>,
+
+    /// How many of the aniSearch entries, which the offline database links to a title, have to be dubbed
+    #[arg(long, value_enum, default_value_t = ValidationPolicy::All)]
+    pub(crate) validation_policy: ValidationPolicy,
+
+    /// Which status a title gets, if its linked entries have different dub statuses
+    #[arg(long, value_enum, default_value_t = StatusAggregation::WorstWins)]
+    pub(crate) status_aggregation: StatusAggregation,
+
+    /// Path to the anime-offline-database
+    #[arg(
+        long,
+        default_value = "../anime-offline-database/anime-offline-database-minified.json"
+    )]
+    pub(crate) database: PathBuf,
+
+    /// Path of the generated dub data
+    #[arg(short, long, default_value = "../data/dubInfo.json")]
+    pub(crate) output: PathBuf,
+
+    /// Also write the dub data of all languages to this file, keyed by language code
+    #[arg(long, value_name = "PATH")]
+    pub(crate) combined_output: Option<PathBuf>,
+
+    /// Also write the licensors, publishers and dubbing studios of the dubbed titles to this file
+    #[arg(long, value_name = "PATH")]
+    pub(crate) companies_output: Option<PathBuf>,
+
+    /// Also write the changes to the previous dub data to this file
+    #[arg(long, value_name = "PATH")]
+    pub(crate) changes_output: Option<PathBuf>,
+
+    /// Also write the changes to the previous dub data as Markdown to this file, e.g. to use it as commit message
+    #[arg(long, value_name = "PATH")]
+    pub(crate) changes_markdown_output: Option<PathBuf>,
+
+    /// Only compare the dub data with the previous one without writing it, and exit with 1 if it would change
+    #[arg(long)]
+    pub(crate) check: bool,
+
+    /// Maximum fraction, by which the number of dubbed titles may shrink compared to the previous dub data
+    #[arg(long, value_name = "FRACTION", default_value_t = 0.1, value_parser = parse_fraction)]
+    pub(crate) max_shrink: f64,
+
+    /// Maximum number of titles, which may no longer be dubbed compared to the previous dub data
+    #[arg(long, value_name = "COUNT", default_value_t = 50)]
+    pub(crate) max_removals: usize,
+
+    /// Minimum number of dubbed titles
+    #[arg(long, value_name = "COUNT", default_value_t = 0)]
+    pub(crate) min_dubbed: usize,
+
+    /// Write the dub data, even if it fails the sanity checks of --max-shrink, --max-removals and --min-dubbed
+    #[arg(long)]
+    pub(crate) force: bool,
+
+    /// TOML file with manual corrections of the links between aniSearch and MyAnimeList
+    #[arg(long, value_name = "PATH")]
+    pub(crate) mappings: Option<PathBuf>,
+
+    /// TOML file with manual corrections of the dub status, which are applied after scraping
+    #[arg(long, value_name = "PATH")]
+    pub(crate) overrides: Option<PathBuf>,
+
+    /// Also write the sources, which list each dubbed title, to this file
+    #[arg(long, value_name = "PATH")]
+    pub(crate) provenance_output: Option<PathBuf>,
+
+    /// Also write dubbed titles without a MyAnimeList id and broken links of the offline database to this file
+    #[arg(long, value_name = "PATH")]
+    pub(crate) unmatched_output: Option<PathBuf>,
+
+    /// Suggest MyAnimeList ids for dubbed titles without one by comparing their titles, year and type
+    #[arg(long)]
+    pub(crate) fuzzy_match: bool,
+
+    /// Link suggestions with at least this score between 0 and 1 without review
+    #[arg(long, value_name = "SCORE", requires = "fuzzy_match", value_parser = parse_fraction)]
+    pub(crate) fuzzy_auto_apply: Option<f64>,
+
+    /// Write the suggestions, which need to be reviewed, to this file
+    #[arg(long, value_name = "PATH", requires = "fuzzy_match")]
+    pub(crate) fuzzy_review_output: Option<PathBuf>,
+
+    /// URL of the aniSearch site, e.g. to use a local mirror
+    #[arg(long, default_value = DEFAULT_BASE_URL)]
+    pub(crate) base_url: String,
+
+    /// URL of the Synchronkartei site
+    #[arg(long, default_value = DEFAULT_SYNCHRONKARTEI_URL)]
+    pub(crate) synchronkartei_url: String,
+
+    /// User-Agent sent to all sources, which should identify the tool and a way to contact its maintainer
+    #[arg(long, default_value = DEFAULT_USER_AGENT)]
+    pub(crate) user_agent: String,
+
+    /// Maximum number of requests in flight at the same time
+    #[arg(long, default_value_t = NonZeroUsize::new(4).unwrap())]
+    pub(crate) concurrency: NonZeroUsize,
+
+    /// Maximum number of requests per second to each site
+    #[arg(long, default_value_t = 1.0, value_parser = parse_requests_per_second)]
+    pub(crate) requests_per_second: f64,
+
+    /// Number of requests which may be sent at once before the rate limit applies
+    #[arg(long, default_value_t = NonZeroU32::new(2).unwrap())]
+    pub(crate) burst: NonZeroU32,
+
+    /// Maximum number of attempts per page, including the first one
+    #[arg(long, default_value_t = NonZeroU32::new(5).unwrap())]
+    pub(crate) max_attempts: NonZeroU32,
+
+    /// Maximum number of seconds spent on retrying a single page
+    #[arg(long, default_value_t = 15 * 60)]
+    pub(crate) max_retry_time: u64,
+
+    /// Number of failed requests in a row, after which the run is aborted
+    #[arg(long, default_value_t = NonZeroU32::new(10).unwrap())]
+    pub(crate) circuit_breaker_threshold: NonZeroU32,
+
+    /// Directory for cached responses
+    #[arg(long, default_value = ".cache/http")]
+    pub(crate) cache_dir: PathBuf,
+
+    /// Do not read or write cached responses
+    #[arg(long)]
+    pub(crate) no_cache: bool,
+
+    /// Seconds for which a cached listing page is used without revalidation
+    #[arg(long, default_value_t = 0)]
+    pub(crate) listing_max_age: u64,
+
+    /// Seconds for which a cached anime page is used without revalidation
+    #[arg(long, default_value_t = 12 * 60 * 60)]
+    pub(crate) detail_max_age: u64,
+
+    /// Archive every fetched page in this directory
+    #[arg(long, value_name = "DIR", conflicts_with = "replay")]
+    pub(crate) record: Option<PathBuf>,
+
+    /// Serve pages exclusively from an archive recorded with --record
+    #[arg(long, value_name = "DIR")]
+    pub(crate) replay: Option<PathBuf>,
+}
+
+fn parse_requests_per_second(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(rps) if rps.is_finite() && rps > 0.0 => Ok(rps),
+        Ok(_) => Err("must be a positive number".to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+fn parse_fraction(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(fraction) if (0.0..=1.0).contains(&fraction) => Ok(fraction),
+        Ok(_) => Err("must be between 0 and 1".to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Source {
+    Anisearch,
+    /// German dubbing database, whose productions are matched by title and year
+    Synchronkartei,
 }
 
-#[derive(Debug, Clone, ValueEnum)]
-pub enum Language {
-    German,
-    English,
-    French,
-    Italian,
-    Spanish,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ValidationPolicy {
+    /// Every linked entry has to be dubbed
+    All,
+    /// One dubbed entry is enough
+    Any,
+    /// More than half of the linked entries have to be dubbed
+    Majority,
 }
 
-impl Language {
-    pub const fn get_anisearch_language(&self) -> &'static str {
+impl ValidationPolicy {
+    /// Whether a title with `validations` of `required` dubbed entries counts as dubbed.
+    pub const fn is_satisfied(&self, validations: u64, required: u64) -> bool {
+        match self {
+            ValidationPolicy::All => validations >= required,
+            ValidationPolicy::Any => validations > 0,
+            ValidationPolicy::Majority => validations * 2 > required,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StatusAggregation {
+    /// A title is as complete as its most complete entry
+    BestWins,
+    /// A title is as complete as its least complete entry, e.g. never released if one entry is
+    WorstWins,
+}
+
+impl StatusAggregation {
+    /// Combines the statuses of the entries linked to a title. Returns `None` without statuses.
+    pub fn resolve(&self, statuses: impl IntoIterator<Item = DubStatus>) -> Option<DubStatus> {
+        let statuses = statuses.into_iter();
+
         match self {
-            Language::German => "de",
-            Language::English => "en",
-            Language::French => "fr",
-            Language::Italian => "it",
-            Language::Spanish => "es",
+            StatusAggregation::BestWins => statuses.max_by_key(DubStatus::rank),
+            StatusAggregation::WorstWins => statuses.min_by_key(DubStatus::rank),
         }
     }
 }
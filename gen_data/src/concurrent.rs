@@ -0,0 +1,20 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Calls `f` with the index and value of every item, using up to `concurrency` threads.
+pub fn for_each<T: Sync>(items: &[T], concurrency: usize, f: impl Fn(usize, &T) + Sync) {
+    let next_index = AtomicUsize::new(0);
+    let threads = concurrency.clamp(1, items.len().max(1));
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                let index = next_index.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(index) else {
+                    break;
+                };
+
+                f(index, item);
+            });
+        }
+    });
+}
@@ -3,7 +3,9 @@ use std::fs::File;
 use std::io::BufReader;
 use std::path::Path;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
 
 pub type Url = String;
 
@@ -32,7 +34,7 @@ pub struct Anime {
     pub tags: Box<[String]>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum Type {
     Tv,
@@ -76,11 +78,16 @@ pub struct License {
     pub url: Url,
 }
 
-pub fn read_database(path: &Path) -> Root {
+pub fn read_database(path: &Path) -> Result<Root> {
+    let io_error = |message: String| Error::Io {
+        path: path.display().to_string(),
+        message,
+    };
+
     // open the file in read-only mode with buffer
-    let file = File::open(path).expect("database could not be found or opened");
+    let file = File::open(path).map_err(|err| io_error(err.to_string()))?;
     let reader = BufReader::new(file);
 
     // read the JSON contents of the file as an instance of `Root`
-    serde_json::from_reader(reader).expect("failed to parse database")
+    serde_json::from_reader(reader).map_err(|err| io_error(err.to_string()))
 }
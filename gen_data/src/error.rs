@@ -0,0 +1,105 @@
+use std::fmt::{Display, Formatter};
+
+/// Everything that can go wrong while generating the dub data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The request could not be sent or the response could not be read
+    Network { url: String, message: String },
+    /// The site answered with an unexpected HTTP status
+    HttpStatus { url: String, status: u16 },
+    /// The site kept answering with 429 Too Many Requests
+    RateLimited { url: String },
+    /// Too many requests in a row failed, so no further requests are sent in this run
+    CircuitOpen { url: String },
+    /// An element, which is required for parsing, is missing on the page
+    SelectorMissing { url: String, selector: String },
+    /// The page could be parsed, but its content is not in the expected format
+    UnexpectedLayout { url: String, reason: String },
+    /// A link could not be turned into the URL of an anime
+    InvalidUrl(String),
+    /// The replay archive has no entry for the requested URL
+    ReplayMiss { url: String },
+    /// robots.txt of the site does not allow fetching the URL
+    Disallowed { url: String },
+    /// Reading or writing a local file failed
+    Io { path: String, message: String },
+    /// A local file could be read, but its content is invalid
+    InvalidFile { path: String, message: String },
+    /// An argument is not valid, which could not be checked while parsing
+    InvalidArgument(String),
+    /// The run has been stopped by a signal after writing a checkpoint
+    Interrupted,
+    /// The dub data would change, which is an error only when checking for changes
+    Changed,
+    /// The dub data lost too many titles, which usually means that a source is broken
+    SanityCheck(Vec<String>),
+}
+
+impl Error {
+    /// Short description of the kind of error, used to group errors in the summary.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Network { .. } => "network error",
+            Error::HttpStatus { .. } => "HTTP error status",
+            Error::RateLimited { .. } => "rate limited",
+            Error::CircuitOpen { .. } => "skipped, site is down",
+            Error::SelectorMissing { .. } => "missing element",
+            Error::UnexpectedLayout { .. } => "unexpected layout",
+            Error::InvalidUrl(_) => "invalid URL",
+            Error::ReplayMiss { .. } => "missing in replay archive",
+            Error::Disallowed { .. } => "disallowed by robots.txt",
+            Error::Io { .. } => "file error",
+            Error::InvalidFile { .. } => "invalid file",
+            Error::InvalidArgument(_) => "invalid argument",
+            Error::Interrupted => "interrupted",
+            Error::Changed => "dub data changed",
+            Error::SanityCheck(_) => "failed sanity check",
+        }
+    }
+
+    /// Process exit code, if the run has to be aborted because of this error.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Changed => 1,
+            Error::InvalidArgument(_) => 2,
+            Error::Network { .. } | Error::HttpStatus { .. } | Error::RateLimited { .. } => 3,
+            Error::SelectorMissing { .. } | Error::UnexpectedLayout { .. } | Error::InvalidUrl(_) => 4,
+            Error::Io { .. } | Error::InvalidFile { .. } => 5,
+            Error::ReplayMiss { .. } => 6,
+            Error::Disallowed { .. } => 7,
+            Error::CircuitOpen { .. } => 8,
+            Error::Interrupted => 9,
+            Error::SanityCheck(_) => 10,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Network { url, message } => write!(f, "request to {url} failed: {message}"),
+            Error::HttpStatus { url, status } => write!(f, "server returned status {status} for: {url}"),
+            Error::RateLimited { url } => write!(f, "rate limited for: {url}"),
+            Error::CircuitOpen { url } => write!(f, "site seems to be down, skipped: {url}"),
+            Error::SelectorMissing { url, selector } => write!(f, "could not find `{selector}` on: {url}"),
+            Error::UnexpectedLayout { url, reason } => write!(f, "unexpected layout of {url}: {reason}"),
+            Error::InvalidUrl(url) => write!(f, "could not format url: {url}"),
+            Error::ReplayMiss { url } => write!(f, "replay archive has no entry for: {url}"),
+            Error::Disallowed { url } => write!(f, "robots.txt does not allow fetching: {url}"),
+            Error::Io { path, message } => write!(f, "could not access {path}: {message}"),
+            Error::InvalidFile { path, message } => write!(f, "invalid {path}: {message}"),
+            Error::InvalidArgument(message) => write!(f, "invalid argument: {message}"),
+            Error::Interrupted => write!(f, "interrupted, continue with --resume"),
+            Error::Changed => write!(f, "dub data would change"),
+            Error::SanityCheck(violations) => write!(
+                f,
+                "dub data failed the sanity checks, write it anyway with --force: {}",
+                violations.join("; ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub type Result<T> = std::result::Result<T, Error>;
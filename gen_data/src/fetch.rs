@@ -0,0 +1,352 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use reqwest::blocking::Response;
+use reqwest::header::{HeaderName, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, RETRY_AFTER};
+use reqwest::StatusCode;
+
+use crate::archive::{ArchiveMode, ArchivedResponse};
+use crate::cache::{CacheEntry, PageClass, ResponseCache};
+use crate::checkpoint;
+use crate::error::{Error, Result};
+use crate::rate_limit::RateLimiter;
+use crate::retry::{parse_retry_after, CircuitBreaker, RetryPolicy};
+use crate::robots::RobotsTxt;
+
+pub const DEFAULT_USER_AGENT: &str = concat!(
+    env!("CARGO_PKG_NAME"),
+    "/",
+    env!("CARGO_PKG_VERSION"),
+    " (+https://github.com/Funami580/MAL-GerDubs)"
+);
+
+/// Fetches pages politely: rate limited, retried, cached and in accordance with robots.txt.
+pub struct Fetcher {
+    client: reqwest::blocking::Client,
+    max_in_flight: usize,
+    rate_limiter: RateLimiter,
+    retry_policy: RetryPolicy,
+    circuit_breaker: CircuitBreaker,
+    /// Token of the user agent, which is matched against robots.txt groups
+    product_token: String,
+    /// robots.txt per origin, fetched once per run
+    robots: Mutex<HashMap<String, Arc<RobotsTxt>>>,
+    cache: Option<ResponseCache>,
+    archive: Option<ArchiveMode>,
+}
+
+pub struct FetchConfig {
+    /// User-Agent header, which should identify this tool and how to reach its maintainer
+    pub user_agent: String,
+    /// Maximum number of requests which may be in flight at the same time
+    pub max_in_flight: usize,
+    /// Maximum number of requests per second to the same host
+    pub requests_per_second: f64,
+    /// Number of requests which may be sent at once before the rate limit applies
+    pub burst: u32,
+    pub retry_policy: RetryPolicy,
+    /// Number of failed requests in a row, after which the site is considered down
+    pub circuit_breaker_threshold: u32,
+    /// Cache for responses, which is revalidated with conditional requests
+    pub cache: Option<ResponseCache>,
+    /// Archive, which fetched pages are recorded to or replayed from
+    pub archive: Option<ArchiveMode>,
+}
+
+impl Default for FetchConfig {
+    fn default() -> Self {
+        Self {
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            max_in_flight: 1,
+            requests_per_second: 1.0,
+            burst: 1,
+            retry_policy: RetryPolicy::default(),
+            circuit_breaker_threshold: 10,
+            cache: None,
+            archive: None,
+        }
+    }
+}
+
+impl Fetcher {
+    pub fn new(config: FetchConfig) -> Self {
+        let client = reqwest::blocking::Client::builder()
+            .user_agent(&config.user_agent)
+            .timeout(Duration::from_secs(20))
+            .connect_timeout(Duration::from_secs(20))
+            .build()
+            .unwrap();
+
+        Self {
+            client,
+            max_in_flight: config.max_in_flight.max(1),
+            rate_limiter: RateLimiter::new(config.requests_per_second, config.burst),
+            retry_policy: config.retry_policy,
+            circuit_breaker: CircuitBreaker::new(config.circuit_breaker_threshold),
+            product_token: config
+                .user_agent
+                .split(|c: char| c == '/' || c.is_whitespace())
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            robots: Mutex::new(HashMap::new()),
+            cache: config.cache,
+            archive: config.archive,
+        }
+    }
+
+    /// Maximum number of requests, which should be sent concurrently with this fetcher
+    pub fn max_in_flight(&self) -> usize {
+        self.max_in_flight
+    }
+
+    /// Fetches and parses a page, which has to be answered with 200 OK.
+    pub fn get_page(&self, url: &str, page_class: PageClass) -> Result<scraper::Html> {
+        let response = match &self.archive {
+            Some(ArchiveMode::Replay(archive)) => archive
+                .replay(url)
+                .ok_or_else(|| Error::ReplayMiss { url: url.to_string() })?,
+            _ => self.fetch_page(url, page_class)?,
+        };
+
+        if let Some(ArchiveMode::Record(archive)) = &self.archive {
+            archive.record(url, &response);
+        }
+
+        match StatusCode::from_u16(response.status) {
+            Ok(StatusCode::OK) => Ok(scraper::Html::parse_document(&response.body)),
+            Ok(StatusCode::TOO_MANY_REQUESTS) => Err(Error::RateLimited { url: url.to_string() }),
+            _ => Err(Error::HttpStatus {
+                url: url.to_string(),
+                status: response.status,
+            }),
+        }
+    }
+
+    /// Returns an error, if robots.txt of the site does not allow fetching the URL
+    /// or if robots.txt could not be fetched.
+    fn check_robots(&self, url: &str) -> Result<()> {
+        let parsed_url = reqwest::Url::parse(url).map_err(|_| Error::InvalidUrl(url.to_string()))?;
+        let origin = parsed_url.origin().ascii_serialization();
+
+        let robots_txt = {
+            // The lock is held while fetching, so robots.txt is only requested once
+            let mut robots = self.robots.lock().unwrap();
+
+            match robots.get(&origin) {
+                Some(robots_txt) => robots_txt.clone(),
+                None => {
+                    // A failed fetch is not remembered, so it is tried again for the next page
+                    let robots_txt = Arc::new(self.fetch_robots(&origin, parsed_url.host_str().unwrap_or_default())?);
+                    robots.insert(origin, robots_txt.clone());
+                    robots_txt
+                }
+            }
+        };
+
+        let path = match parsed_url.query() {
+            Some(query) => format!("{}?{}", parsed_url.path(), query),
+            None => parsed_url.path().to_string(),
+        };
+
+        if robots_txt.is_allowed(&self.product_token, &path) {
+            Ok(())
+        } else {
+            Err(Error::Disallowed { url: url.to_string() })
+        }
+    }
+
+    fn fetch_robots(&self, origin: &str, host: &str) -> Result<RobotsTxt> {
+        let robots_url = format!("{origin}/robots.txt");
+        let res = self.send(&robots_url, &[])?;
+        let status = res.status();
+
+        let robots_txt = if status.is_success() {
+            let text = res.text().map_err(|err| Error::Network {
+                url: robots_url.clone(),
+                message: err.to_string(),
+            })?;
+
+            RobotsTxt::parse(&text)
+        } else if status.is_client_error() {
+            // No robots.txt means that there are no restrictions
+            RobotsTxt::allow_all()
+        } else {
+            return Err(Error::HttpStatus {
+                url: robots_url,
+                status: status.as_u16(),
+            });
+        };
+
+        if let Some(crawl_delay) = robots_txt.crawl_delay(&self.product_token) {
+            log::info!("Using crawl delay of {:?} for: {}", crawl_delay, host);
+            self.rate_limiter.limit_host(host, crawl_delay);
+        }
+
+        Ok(robots_txt)
+    }
+
+    /// Fetches the page from the cache or from the site. Error responses are returned as well.
+    fn fetch_page(&self, url: &str, page_class: PageClass) -> Result<ArchivedResponse> {
+        fn cached_response(entry: CacheEntry) -> ArchivedResponse {
+            let headers = [(ETAG, entry.etag), (LAST_MODIFIED, entry.last_modified)]
+                .into_iter()
+                .filter_map(|(name, value)| Some((name.to_string(), value?)))
+                .collect();
+
+            ArchivedResponse {
+                status: StatusCode::OK.as_u16(),
+                headers,
+                body: entry.body,
+            }
+        }
+
+        let cached = self.cache.as_ref().and_then(|cache| cache.load(url));
+
+        if let (Some(cache), Some(entry)) = (&self.cache, &cached) {
+            if cache.is_fresh(entry, page_class) {
+                log::debug!("Using cached page for: {}", url);
+                return Ok(cached_response(entry.clone()));
+            }
+        }
+
+        self.check_robots(url)?;
+
+        let conditional_headers: Vec<(HeaderName, &str)> = cached
+            .iter()
+            .flat_map(|entry| [(IF_NONE_MATCH, &entry.etag), (IF_MODIFIED_SINCE, &entry.last_modified)])
+            .filter_map(|(name, value)| Some((name, value.as_deref()?)))
+            .collect();
+        let res = self.send(url, &conditional_headers)?;
+        let status = res.status();
+
+        if status == StatusCode::NOT_MODIFIED {
+            if let (Some(cache), Some(entry)) = (&self.cache, &cached) {
+                let mut entry = entry.clone();
+                entry.fetched_at = crate::cache::now_secs();
+                cache.store(&entry);
+
+                return Ok(cached_response(entry));
+            }
+        }
+
+        let headers: BTreeMap<String, String> = res
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+
+        let body = res.text().map_err(|err| Error::Network {
+            url: url.to_string(),
+            message: err.to_string(),
+        })?;
+
+        if status == StatusCode::OK {
+            if let Some(cache) = &self.cache {
+                cache.store(&CacheEntry {
+                    url: url.to_string(),
+                    etag: headers.get(ETAG.as_str()).cloned(),
+                    last_modified: headers.get(LAST_MODIFIED.as_str()).cloned(),
+                    fetched_at: crate::cache::now_secs(),
+                    body: body.clone(),
+                });
+            }
+        }
+
+        Ok(ArchivedResponse {
+            status: status.as_u16(),
+            headers,
+            body,
+        })
+    }
+
+    /// Sends a GET request, which is retried with backoff after transport errors, 429 Too Many Requests
+    /// and server errors. Any other response is returned, e.g. 404 Not Found.
+    fn send(&self, url: &str, headers: &[(HeaderName, &str)]) -> Result<Response> {
+        let host = reqwest::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_default();
+        let start = Instant::now();
+        let mut attempt: u32 = 0;
+
+        loop {
+            if self.circuit_breaker.is_open() {
+                return Err(Error::CircuitOpen { url: url.to_string() });
+            }
+
+            attempt += 1;
+            self.rate_limiter.acquire(&host)?;
+
+            let mut request = self.client.get(url);
+
+            for (name, value) in headers {
+                request = request.header(name, *value);
+            }
+
+            // Decide whether and how long to wait before the next attempt
+            let (err, retry_delay) = match request.send() {
+                Err(err) => {
+                    let delay = self.retry_policy.backoff(self.retry_policy.base_delay, attempt);
+                    let err = Error::Network {
+                        url: url.to_string(),
+                        message: err.to_string(),
+                    };
+
+                    (err, delay)
+                }
+                Ok(res) if res.status() == StatusCode::TOO_MANY_REQUESTS || res.status().is_server_error() => {
+                    let status = res.status();
+                    let retry_after = res
+                        .headers()
+                        .get(RETRY_AFTER)
+                        .and_then(|value| value.to_str().ok())
+                        .and_then(parse_retry_after);
+
+                    if status == StatusCode::TOO_MANY_REQUESTS {
+                        let delay = retry_after.unwrap_or_else(|| {
+                            self.retry_policy
+                                .backoff(self.retry_policy.rate_limited_base_delay, attempt)
+                        });
+                        let err = Error::RateLimited { url: url.to_string() };
+
+                        (err, delay)
+                    } else {
+                        let delay = retry_after
+                            .unwrap_or_else(|| self.retry_policy.backoff(self.retry_policy.base_delay, attempt));
+                        let err = Error::HttpStatus {
+                            url: url.to_string(),
+                            status: status.as_u16(),
+                        };
+
+                        (err, delay)
+                    }
+                }
+                Ok(res) => {
+                    self.circuit_breaker.record_success();
+                    return Ok(res);
+                }
+            };
+            // Being rate limited does not mean that the site is down
+            if !matches!(err, Error::RateLimited { .. }) && self.circuit_breaker.record_failure() {
+                log::error!("Too many requests in a row failed, {} seems to be down", host);
+            }
+
+            if attempt >= self.retry_policy.max_attempts
+                || start.elapsed() + retry_delay > self.retry_policy.max_total_time
+            {
+                return Err(err);
+            }
+
+            log::info!(
+                "{}, retrying in {:.1}s (attempt {}/{})...",
+                err,
+                retry_delay.as_secs_f64(),
+                attempt + 1,
+                self.retry_policy.max_attempts
+            );
+            checkpoint::sleep(retry_delay)?;
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+
+/// The languages, which are known without a custom table.
+const DEFAULT_TABLE: &str = include_str!("../languages.toml");
+
+/// A dub language of aniSearch with the keywords of its dub statuses.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Language {
+    /// aniSearch language code, e.g. `de`
+    pub code: String,
+    /// English name, which may be used instead of the code, e.g. `German`
+    pub name: String,
+    pub completed: Vec<String>,
+    pub upcoming: Vec<String>,
+    pub never_released: Vec<String>,
+}
+
+impl Language {
+    /// Whether the status label of a dub block, e.g. `Completed`, says that the dub is complete.
+    pub fn is_completed(&self, status_label: &str) -> bool {
+        is_label(status_label, &self.completed)
+    }
+
+    /// Whether the status label of a dub block says that the dub has been announced.
+    pub fn is_upcoming(&self, status_label: &str) -> bool {
+        is_label(status_label, &self.upcoming)
+    }
+
+    /// Whether the title of a dub block says that the dub has never been released.
+    pub fn is_never_released(&self, title: &str) -> bool {
+        contains_words(title, &self.never_released)
+    }
+}
+
+/// Whether the whole label is one of the keywords, so that e.g. `incompleto` does not count as `completo`.
+fn is_label(label: &str, keywords: &[String]) -> bool {
+    keywords.contains(&label.trim().to_lowercase())
+}
+
+/// Whether the text contains one of the keywords as a sequence of whole words.
+fn contains_words(text: &str, keywords: &[String]) -> bool {
+    fn words(text: &str) -> Vec<&str> {
+        text.split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect()
+    }
+
+    let text = text.to_lowercase();
+    let text_words = words(&text);
+
+    keywords.iter().any(|keyword| {
+        let keyword_words = words(keyword);
+        !keyword_words.is_empty()
+            && text_words
+                .windows(keyword_words.len())
+                .any(|window| window == keyword_words)
+    })
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+pub struct LanguageTable {
+    #[serde(default, rename = "language")]
+    languages: Vec<Language>,
+}
+
+impl LanguageTable {
+    /// Reads a table of languages from a TOML file with one `[[language]]` table per language:
+    ///
+    /// ```toml
+    /// [[language]]
+    /// code = "de"
+    /// name = "German"
+    /// completed = ["completed", "abgeschlossen"]
+    /// upcoming = ["upcoming", "angekündigt"]
+    /// never_released = ["never released", "nie erschienen"]
+    /// ```
+    pub fn read(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).map_err(|err| Error::Io {
+            path: path.display().to_string(),
+            message: err.to_string(),
+        })?;
+
+        Self::parse(&text).map_err(|message| Error::InvalidFile {
+            path: path.display().to_string(),
+            message,
+        })
+    }
+
+    fn parse(text: &str) -> std::result::Result<Self, String> {
+        let mut table: LanguageTable = toml::from_str(text).map_err(|err| err.message().to_string())?;
+
+        for language in table.languages.iter_mut() {
+            // The code ends up in URLs and selectors
+            if language.code.is_empty() || !language.code.chars().all(|c| c.is_ascii_lowercase() || c == '-') {
+                return Err(format!("invalid language code `{}`", language.code));
+            }
+
+            for keywords in [
+                &mut language.completed,
+                &mut language.upcoming,
+                &mut language.never_released,
+            ] {
+                if keywords.iter().any(|keyword| keyword.trim().is_empty()) {
+                    return Err(format!("empty status keyword for language `{}`", language.code));
+                }
+
+                for keyword in keywords.iter_mut() {
+                    *keyword = keyword.trim().to_lowercase();
+                }
+            }
+        }
+
+        Ok(table)
+    }
+
+    /// Finds a language by its code or name, ignoring case.
+    pub fn find(&self, code_or_name: &str) -> Option<&Language> {
+        self.languages.iter().find(|language| {
+            language.code.eq_ignore_ascii_case(code_or_name) || language.name.eq_ignore_ascii_case(code_or_name)
+        })
+    }
+
+    /// Codes of all languages in the table.
+    pub fn codes(&self) -> impl Iterator<Item = &str> {
+        self.languages.iter().map(|language| language.code.as_str())
+    }
+}
+
+impl Default for LanguageTable {
+    fn default() -> Self {
+        Self::parse(DEFAULT_TABLE).expect("the built-in language table is valid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LanguageTable;
+
+    #[test]
+    fn test_default_table() {
+        let table = LanguageTable::default();
+
+        assert_eq!(table.find("de").map(|language| language.name.as_str()), Some("German"));
+        assert_eq!(table.find("german").map(|language| language.code.as_str()), Some("de"));
+        assert_eq!(table.find("PT").map(|language| language.code.as_str()), Some("pt"));
+        assert!(table.find("xx").is_none());
+    }
+
+    #[test]
+    fn test_status_keywords() {
+        let table = LanguageTable::default();
+        let portuguese = table.find("pt").unwrap();
+
+        assert!(portuguese.is_completed(" Completo "));
+        assert!(!portuguese.is_completed("incompleto"));
+        assert!(!table.find("pl").unwrap().is_completed("niezakończony"));
+        assert!(!table.find("de").unwrap().is_completed("nicht abgeschlossen"));
+
+        let english = table.find("en").unwrap();
+        assert!(english.is_upcoming("Upcoming"));
+        assert!(english.is_never_released("Anime 2852 Never released in this language"));
+        assert!(!english.is_never_released("Never releasedd"));
+    }
+
+    #[test]
+    fn test_parse() {
+        let table = LanguageTable::parse(
+            r#"
+            [[language]]
+            code = "sv"
+            name = "Swedish"
+            completed = ["Completed", "Avslutad"]
+            upcoming = ["upcoming"]
+            never_released = []
+            "#,
+        )
+        .unwrap();
+        assert_eq!(table.find("swedish").unwrap().completed, ["completed", "avslutad"]);
+
+        let invalid_code = r#"
+            [[language]]
+            code = "de\"]"
+            name = "German"
+            completed = []
+            upcoming = []
+            never_released = []
+            "#;
+        assert!(LanguageTable::parse(invalid_code).is_err());
+    }
+}
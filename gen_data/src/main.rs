@@ -1,21 +1,53 @@
-use std::cell::RefCell;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::ops::Deref;
-use std::path::Path;
-use std::rc::Rc;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use anisearch::{AnisearchClient, DubStatus, DubbedAnime};
+use anisearch::AnisearchClient;
+use archive::{Archive, ArchiveMode};
+use cache::ResponseCache;
+use changes::{Changes, Thresholds};
+use checkpoint::Checkpointer;
 use clap::Parser;
-use database::Root;
+use database::{Anime, Root};
+use error::Error;
+use fetch::FetchConfig;
+use language::{Language, LanguageTable};
+use mapping::{MappingAction, MappingOverride};
+use matching::FuzzyMatcher;
+use output::{BrokenLink, MatchCandidate, MatchReview, PartialMatch, StatusConflict, UnmatchedAnime};
+use overrides::{Override, OverrideTarget, ResolvedOverride};
+use retry::RetryPolicy;
+use source::{DubInfo, DubSource, DubStatus, ListedAnime, SourceResult};
+use state::{EntryState, State};
+use synchronkartei::SynchronkarteiClient;
 
 mod anisearch;
+mod archive;
+mod cache;
+mod changes;
+mod checkpoint;
 mod cli;
+mod concurrent;
 mod database;
+mod error;
+mod fetch;
+mod language;
 mod logger;
+mod mapping;
+mod matching;
 mod output;
+mod overrides;
+mod rate_limit;
+mod retry;
+mod robots;
+mod source;
+mod state;
+mod synchronkartei;
 
-fn main() {
+fn main() -> ExitCode {
     // Parse arguments
     let args = cli::Args::parse();
 
@@ -26,29 +58,607 @@ fn main() {
     indicatif_log_bridge::LogWrapper::new(multi.clone(), logger)
         .try_init()
         .unwrap();
+    checkpoint::handle_signals();
+
+    let failures: Mutex<Vec<Error>> = Mutex::new(Vec::new());
+    let result = run(&args, &multi, &failures);
+
+    log_failure_summary(&failures.into_inner().unwrap());
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err @ Error::Changed) => {
+            log::info!("The dub data would change");
+            ExitCode::from(err.exit_code())
+        }
+        Err(err) => {
+            log::error!("Aborting: {}", err);
+            ExitCode::from(err.exit_code())
+        }
+    }
+}
+
+/// Generates the dub data. Errors, which do not abort the run, are collected in `failures`.
+fn run(args: &cli::Args, multi: &indicatif::MultiProgress, failures: &Mutex<Vec<Error>>) -> error::Result<()> {
+    let language_table = match &args.language_table {
+        Some(language_table_path) => LanguageTable::read(language_table_path)?,
+        None => LanguageTable::default(),
+    };
+    let mut languages: Vec<&Language> = Vec::with_capacity(args.language.len());
+
+    for code_or_name in args.language.iter() {
+        let language = language_table.find(code_or_name).ok_or_else(|| {
+            Error::InvalidArgument(format!(
+                "unknown language `{}`, expected one of: {}",
+                code_or_name,
+                language_table.codes().collect::<Vec<_>>().join(", ")
+            ))
+        })?;
+
+        if !languages.contains(&language) {
+            languages.push(language);
+        }
+    }
 
     // Read database
-    let db_path = Path::new("../anime-offline-database/anime-offline-database-minified.json");
-    let output_path = Path::new("../data/dubInfo.json");
-    let root = database::read_database(db_path);
-    assert!(root.data.len() > 0);
+    let db_path = args.database.as_path();
+    let root = database::read_database(db_path)?;
+
+    if root.data.is_empty() {
+        return Err(Error::Io {
+            path: db_path.display().to_string(),
+            message: "database contains no anime".to_string(),
+        });
+    }
+
+    // Read manual corrections before scraping, so that mistakes are noticed early
+    let overrides = match &args.overrides {
+        Some(overrides_path) => Some(overrides::read_overrides(overrides_path)?),
+        None => None,
+    };
+    let resolved_overrides = match (&args.overrides, &overrides) {
+        (Some(overrides_path), Some(overrides)) => {
+            if let Some(unknown) = overrides
+                .iter()
+                .filter_map(|r#override| r#override.language.as_deref())
+                .find(|&code| !language_table.codes().any(|known| known == code))
+            {
+                return Err(Error::InvalidFile {
+                    path: overrides_path.display().to_string(),
+                    message: format!("unknown language code `{unknown}`"),
+                });
+            }
+
+            Some(resolve_overrides(&root, overrides, overrides_path)?)
+        }
+        _ => None,
+    };
+
+    let mappings = match &args.mappings {
+        Some(mappings_path) => mapping::read_mappings(mappings_path, &root)?,
+        None => Vec::new(),
+    };
+
+    let fuzzy_matcher = args
+        .fuzzy_match
+        .then(|| matching::FuzzyMatcher::new(&root, args.fuzzy_auto_apply));
+
+    // Titles of the MyAnimeList ids, with which the changes are reported
+    let titles: HashMap<u64, &str> = root
+        .data
+        .iter()
+        .filter_map(|anime| Some((get_mal_ids(anime)?, anime.title.as_str())))
+        .flat_map(|(mal_ids, title)| mal_ids.into_vec().into_iter().map(move |mal_id| (mal_id, title)))
+        .collect();
+
+    let inputs = Inputs {
+        root: &root,
+        titles,
+        overrides: resolved_overrides.as_deref(),
+        mappings: &mappings,
+        fuzzy_matcher: fuzzy_matcher.as_ref(),
+    };
+
+    // The offline database and the cache are shared by all languages
+    let mut combined = BTreeMap::new();
+    let mut changed = false;
+
+    for &language in languages.iter() {
+        let language_code = language.code.as_str();
+        let output_paths = OutputPaths::new(args, (languages.len() > 1).then_some(language_code));
+
+        log::info!("Generating dub data for language: {}", language_code);
+        let (output, language_changed) = run_language(args, language, &inputs, &output_paths, multi, failures)?;
+        changed |= language_changed;
+        combined.insert(language_code, output);
+    }
+
+    if args.check {
+        return if changed { Err(Error::Changed) } else { Ok(()) };
+    }
+
+    if let Some(combined_output_path) = &args.combined_output {
+        output::write_combined(combined_output_path, &combined)?;
+    }
+
+    Ok(())
+}
+
+/// Data, which is read once and shared by all languages.
+struct Inputs<'a> {
+    root: &'a Root,
+    titles: HashMap<u64, &'a str>,
+    overrides: Option<&'a [ResolvedOverride<'a>]>,
+    mappings: &'a [MappingOverride],
+    fuzzy_matcher: Option<&'a FuzzyMatcher<'a>>,
+}
+
+/// Paths of the files written for a single language.
+struct OutputPaths {
+    output: PathBuf,
+    companies: Option<PathBuf>,
+    provenance: Option<PathBuf>,
+    unmatched: Option<PathBuf>,
+    fuzzy_review: Option<PathBuf>,
+    changes: Option<PathBuf>,
+    changes_markdown: Option<PathBuf>,
+    state: Option<PathBuf>,
+    checkpoint: PathBuf,
+}
+
+impl OutputPaths {
+    /// Uses the paths of the arguments, with the language code inserted if given,
+    /// e.g. `dubInfo.de.json` instead of `dubInfo.json`.
+    fn new(args: &cli::Args, language_code: Option<&str>) -> Self {
+        let for_language = |path: &Path| match language_code {
+            Some(language_code) => {
+                let mut file_name = path.file_stem().unwrap_or_default().to_os_string();
+                file_name.push(format!(".{language_code}"));
+
+                if let Some(extension) = path.extension() {
+                    file_name.push(".");
+                    file_name.push(extension);
+                }
+
+                path.with_file_name(file_name)
+            }
+            None => path.to_path_buf(),
+        };
+
+        Self {
+            output: for_language(&args.output),
+            companies: args.companies_output.as_deref().map(for_language),
+            provenance: args.provenance_output.as_deref().map(for_language),
+            unmatched: args.unmatched_output.as_deref().map(for_language),
+            fuzzy_review: args.fuzzy_review_output.as_deref().map(for_language),
+            changes: args.changes_output.as_deref().map(for_language),
+            changes_markdown: args.changes_markdown_output.as_deref().map(for_language),
+            state: args.state.as_deref().map(for_language),
+            checkpoint: for_language(&args.checkpoint),
+        }
+    }
+}
+
+/// Generates and writes the dub data of a single language. Returns it and whether it differs from the previous one.
+fn run_language(
+    args: &cli::Args,
+    language: &Language,
+    inputs: &Inputs,
+    output_paths: &OutputPaths,
+    multi: &indicatif::MultiProgress,
+    failures: &Mutex<Vec<Error>>,
+) -> error::Result<(output::Output, bool)> {
+    let root = inputs.root;
+    let output_path = output_paths.output.as_path();
+    let previous_state = match &output_paths.state {
+        Some(state_path) => state::read_state(state_path)?,
+        None => State::default(),
+    };
+    let checkpointer = Checkpointer::new(output_paths.checkpoint.clone(), args.resume)?;
+
+    // Check every source, ordered by precedence
+    let mut source_kinds: Vec<cli::Source> = Vec::with_capacity(args.sources.len());
+
+    for &source_kind in args.sources.iter() {
+        if !source_kinds.contains(&source_kind) {
+            source_kinds.push(source_kind);
+        }
+    }
+
+    let mut source_results = Vec::with_capacity(source_kinds.len());
+    let mut source_names = Vec::with_capacity(source_kinds.len());
+
+    for source_kind in source_kinds {
+        let source: Box<dyn DubSource> = match source_kind {
+            cli::Source::Anisearch => Box::new(AnisearchClient::new(language, &args.base_url, fetch_config(args))),
+            cli::Source::Synchronkartei if language.code != "de" => {
+                log::warn!("Synchronkartei only knows German dubs, skipping it");
+                continue;
+            }
+            cli::Source::Synchronkartei => {
+                Box::new(SynchronkarteiClient::new(&args.synchronkartei_url, fetch_config(args)))
+            }
+        };
+
+        // The mappings link aniSearch entries, and only those can lack a link in the offline database
+        let (source_mappings, source_fuzzy_matcher) = match source_kind {
+            cli::Source::Anisearch => (inputs.mappings, inputs.fuzzy_matcher),
+            _ => (&[][..], None),
+        };
+
+        source_names.push(source.name());
+        let source_result = check_source(
+            source.as_ref(),
+            root,
+            SourceConfig {
+                mappings: source_mappings,
+                fuzzy_matcher: source_fuzzy_matcher,
+                validation_policy: args.validation_policy,
+                status_aggregation: args.status_aggregation,
+                previous_state: &previous_state,
+                recheck_after: (!args.full).then(|| chrono::Duration::days(args.recheck_after.into())),
+                checkpointer: &checkpointer,
+            },
+            multi,
+            failures,
+        );
+
+        match source_result {
+            Ok(source_result) => source_results.push(source_result),
+            Err(err) => {
+                // Keep the progress, so that the run can be continued with --resume
+                match checkpointer.write() {
+                    Ok(()) => log::info!("Saved the progress to: {}", output_paths.checkpoint.display()),
+                    Err(write_err) => log::error!("Failed to write checkpoint: {}", write_err),
+                }
+
+                return Err(err);
+            }
+        }
+    }
+
+    let mut merged = source::merge(source_results);
+
+    // Apply manual corrections after scraping
+    if let Some(resolved) = inputs.overrides {
+        // Overrides without a language apply to all languages
+        let resolved: Vec<ResolvedOverride> = resolved
+            .iter()
+            .filter(|(r#override, _)| {
+                r#override
+                    .language
+                    .as_ref()
+                    .map_or(true, |override_language| override_language == &language.code)
+            })
+            .cloned()
+            .collect();
+        let report = overrides::apply(&resolved, &mut merged, chrono::Local::now().date_naive());
+
+        for (r#override, mal_ids) in resolved.iter() {
+            if report.applied.contains(r#override) {
+                for mal_id in mal_ids.iter().filter(|mal_id| merged.dubbed.contains(mal_id)) {
+                    merged.provenance.entry(*mal_id).or_default().insert("override");
+                }
+            }
+        }
+
+        let dubbed = &merged.dubbed;
+        merged.provenance.retain(|mal_id, _| dubbed.contains(mal_id));
+
+        log_override_summary(&report);
+    }
+
+    let SourceResult {
+        dubbed,
+        incomplete,
+        upcoming,
+        never_released,
+        unknown,
+        mut progress,
+        mut companies,
+        provenance,
+        unmatched,
+        broken_links,
+        partial_matches,
+        status_conflicts,
+        match_reviews,
+        state,
+    } = merged;
+
+    progress.retain(|mal_id, _| dubbed.contains(mal_id));
+
+    // Titles, which the source with the highest precedence does not know about
+    if source_names.len() > 1 {
+        for (mal_id, sources) in provenance.iter() {
+            if !sources.contains(source_names[0]) {
+                log::info!(
+                    "Dub is only known to {}: https://myanimelist.net/anime/{}",
+                    sources.iter().copied().collect::<Vec<_>>().join(", "),
+                    mal_id
+                );
+            }
+        }
+    }
+
+    if !unmatched.is_empty() || !broken_links.is_empty() {
+        log::warn!(
+            "{} dubbed titles have no MyAnimeList id and {} links of the offline database are broken",
+            unmatched.len(),
+            broken_links.len()
+        );
+    }
+
+    if !partial_matches.is_empty() {
+        log::warn!(
+            "{} titles are only partially listed as dubbed, of which {} count as dubbed",
+            partial_matches.len(),
+            partial_matches
+                .iter()
+                .filter(|partial_match| partial_match.dubbed)
+                .count()
+        );
+    }
+
+    if !status_conflicts.is_empty() {
+        log::warn!(
+            "{} titles have linked entries with different dub statuses",
+            status_conflicts.len()
+        );
+    }
 
-    // Process...
-    let mut anisearch_map = get_anisearch_map(&root);
-    let mut dubbed_mal_ids: HashSet<u64> = HashSet::new();
-    let mut dubbed_anisearch_urls: HashSet<String> = HashSet::new();
-    let mut dub_incomplete_mal_ids: HashSet<u64> = HashSet::new();
-    let mut dub_never_released_mal_ids: HashSet<u64> = HashSet::new();
+    let output = output::Output {
+        dubbed: dubbed.iter().copied().collect(),
+        incomplete: incomplete.into_iter().collect(),
+        upcoming: upcoming.into_iter().collect(),
+        never_released: never_released.into_iter().collect(),
+        unknown: unknown.into_iter().collect(),
+        progress,
+    };
+    let previous_output = output::read_output(output_path)
+        .unwrap_or_else(|err| {
+            log::warn!(
+                "Could not read the previous dub data, so all of it counts as changed: {}",
+                err
+            );
+            None
+        })
+        .unwrap_or_default();
+    let changes = Changes::new(&previous_output, &output, &inputs.titles);
+    let changed = output != previous_output;
+    log::info!("Changes to the previous dub data: {}", changes.summary());
+
+    if let Some(changes_output_path) = &output_paths.changes {
+        output::write_changes(changes_output_path, &changes)?;
+    }
+
+    if let Some(changes_markdown_output_path) = &output_paths.changes_markdown {
+        output::write_changes_markdown(changes_markdown_output_path, &changes)?;
+    }
+
+    // A broken source must not wipe out the dub data
+    let thresholds = Thresholds {
+        max_shrink: args.max_shrink,
+        max_removals: args.max_removals,
+        min_dubbed: args.min_dubbed,
+    };
+    let violations = thresholds.violations(&previous_output, &output, &changes);
+
+    if !violations.is_empty() {
+        if !args.force {
+            return Err(Error::SanityCheck(violations));
+        }
+
+        for violation in violations.iter() {
+            log::warn!("Writing the dub data anyway: {}", violation);
+        }
+    }
+
+    // Only the changes are written when checking for them
+    if args.check {
+        checkpointer.remove();
+        return Ok((output, changed));
+    }
+
+    if let Some(unmatched_output_path) = &output_paths.unmatched {
+        output::write_mapping_report(
+            unmatched_output_path,
+            &output::MappingReport {
+                unmatched,
+                broken_links,
+                partial_matches,
+                status_conflicts,
+            },
+        )?;
+    }
+
+    if let Some(fuzzy_review_output_path) = &output_paths.fuzzy_review {
+        output::write_match_reviews(fuzzy_review_output_path, &match_reviews)?;
+    }
+
+    if let Some(provenance_output_path) = &output_paths.provenance {
+        output::write_provenance(provenance_output_path, &provenance)?;
+    }
+
+    if let Some(companies_output_path) = &output_paths.companies {
+        for company_titles in companies.values_mut() {
+            for mal_ids in [
+                &mut company_titles.licensor,
+                &mut company_titles.publisher,
+                &mut company_titles.dub_studio,
+            ] {
+                mal_ids.retain(|mal_id| dubbed.contains(mal_id));
+            }
+        }
+
+        companies.retain(|_, company_titles| {
+            !(company_titles.licensor.is_empty()
+                && company_titles.publisher.is_empty()
+                && company_titles.dub_studio.is_empty())
+        });
+
+        output::write_companies(companies_output_path, &companies)?;
+    }
+
+    if let Some(state_path) = &output_paths.state {
+        output::write_state(state_path, &State { entries: state })?;
+    }
 
-    let anisearch_client = AnisearchClient::new(&args.language);
+    output::write_output(output_path, &output)?;
+    checkpointer.remove();
 
-    log::info!("Checking dubbed anime page 1/??...");
-    let page1_results = anisearch_client.get_dubbed_anime_list(1).unwrap();
-    process_dubbed_page(&mut dubbed_mal_ids, &mut anisearch_map, &page1_results);
-    dubbed_anisearch_urls.extend(page1_results.anisearch_urls.into_vec());
+    Ok((output, changed))
+}
+
+/// Configuration for fetching pages, shared by all sources.
+fn fetch_config(args: &cli::Args) -> FetchConfig {
+    FetchConfig {
+        user_agent: args.user_agent.clone(),
+        max_in_flight: args.concurrency.get(),
+        requests_per_second: args.requests_per_second,
+        burst: args.burst.get(),
+        retry_policy: RetryPolicy {
+            max_attempts: args.max_attempts.get(),
+            max_total_time: Duration::from_secs(args.max_retry_time),
+            ..Default::default()
+        },
+        circuit_breaker_threshold: args.circuit_breaker_threshold.get(),
+        cache: (!args.no_cache).then(|| {
+            ResponseCache::new(
+                &args.cache_dir,
+                Duration::from_secs(args.listing_max_age),
+                Duration::from_secs(args.detail_max_age),
+            )
+        }),
+        archive: match (&args.record, &args.replay) {
+            (Some(dir), _) => Some(ArchiveMode::Record(Archive::new(dir))),
+            (_, Some(dir)) => Some(ArchiveMode::Replay(Archive::new(dir))),
+            _ => None,
+        },
+    }
+}
+
+/// Resolves the MyAnimeList ids of every override. aniSearch ids have to be linked in the offline database.
+fn resolve_overrides<'a>(
+    root: &Root,
+    overrides: &'a [Override],
+    overrides_path: &Path,
+) -> error::Result<Vec<ResolvedOverride<'a>>> {
+    let anisearch_map = get_source_map(root, anisearch::ANIME_URL_PREFIX);
+
+    overrides
+        .iter()
+        .map(|r#override| {
+            let mal_ids = match r#override.target {
+                OverrideTarget::Mal(mal_id) => Box::from([mal_id]),
+                OverrideTarget::Anisearch(_) => {
+                    let anisearch_url = r#override.target.to_string();
+
+                    match anisearch_map.get(anisearch_url.as_str()) {
+                        Some(anime_entry_mutex) => anime_entry_mutex.lock().unwrap().mal_ids.clone(),
+                        None => {
+                            return Err(Error::InvalidFile {
+                                path: overrides_path.display().to_string(),
+                                message: format!("{} is not in the offline database", anisearch_url),
+                            })
+                        }
+                    }
+                }
+            };
+
+            Ok((r#override, mal_ids))
+        })
+        .collect()
+}
+
+fn log_override_summary(report: &overrides::OverrideReport) {
+    log::info!("Applied {} override(s):", report.applied.len());
+
+    for r#override in report.applied.iter() {
+        log::info!("  {:?} {}: {}", r#override.status, r#override.target, r#override.reason);
+    }
+
+    if !report.redundant.is_empty() {
+        log::warn!(
+            "{} override(s) are redundant, because the sources agree with them:",
+            report.redundant.len()
+        );
+
+        for r#override in report.redundant.iter() {
+            log::warn!("  {:?} {}: {}", r#override.status, r#override.target, r#override.reason);
+        }
+    }
+
+    if !report.expired.is_empty() {
+        log::warn!(
+            "{} override(s) have expired and were not applied:",
+            report.expired.len()
+        );
+
+        for r#override in report.expired.iter() {
+            log::warn!(
+                "  {:?} {} (expired on {}): {}",
+                r#override.status,
+                r#override.target,
+                r#override.expires.unwrap_or_default(),
+                r#override.reason
+            );
+        }
+    }
+}
+
+/// Options for checking a single source.
+struct SourceConfig<'a> {
+    /// Manual corrections of the links to the offline database
+    mappings: &'a [MappingOverride],
+    fuzzy_matcher: Option<&'a FuzzyMatcher<'a>>,
+    validation_policy: cli::ValidationPolicy,
+    status_aggregation: cli::StatusAggregation,
+    /// Dub infos of the previous run
+    previous_state: &'a State,
+    /// Time after which a complete dub is checked again. Without it, every dub is checked again.
+    recheck_after: Option<chrono::Duration>,
+    /// Progress of an aborted run, which is also updated by this run
+    checkpointer: &'a Checkpointer,
+}
+
+/// Collects the dubbed anime of a single source and checks whether their dubs are complete.
+fn check_source(
+    source: &dyn DubSource,
+    root: &Root,
+    config: SourceConfig,
+    multi: &indicatif::MultiProgress,
+    failures: &Mutex<Vec<Error>>,
+) -> error::Result<SourceResult> {
+    log::info!("Checking dubbed anime on {}...", source.name());
+
+    let checkpointer = config.checkpointer;
+    let resumed = checkpointer.source(source.name());
+    let listed_anime: Mutex<Vec<ListedAnime>> = Mutex::new(resumed.listed_anime);
+    let dub_statuses: Mutex<HashMap<&str, DubStatus>> = Mutex::new(HashMap::new());
+    let dub_infos: Mutex<HashMap<String, DubInfo>> = Mutex::new(HashMap::new());
+    let broken_links: Mutex<Vec<BrokenLink>> = Mutex::new(Vec::new());
+
+    // Page 1 tells the number of pages, unless it is known from the checkpoint
+    let total_pages = match resumed.total_pages {
+        Some(total_pages) if resumed.visited_pages.contains(&1) => {
+            log::info!(
+                "Resuming with {} of {} dubbed anime pages checked",
+                resumed.visited_pages.len(),
+                total_pages
+            );
+            total_pages
+        }
+        _ => {
+            log::info!("Checking dubbed anime page 1/??...");
+            let page1_results = source.get_dubbed_anime_list(1)?;
+            checkpointer.record_page(source.name(), 1, page1_results.total_pages, &page1_results.entries);
+            listed_anime.lock().unwrap().extend(page1_results.entries.into_vec());
+            page1_results.total_pages
+        }
+    };
 
     let progress_bar = {
-        let pb = indicatif::ProgressBar::new(page1_results.total_pages);
+        let pb = indicatif::ProgressBar::new(total_pages);
         pb.set_style(
             indicatif::ProgressStyle::with_template(
                 "{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] ({eta})",
@@ -59,163 +669,862 @@ fn main() {
         multi.add(pb)
     };
 
-    progress_bar.inc(1);
+    progress_bar.inc(1 + resumed.visited_pages.iter().filter(|&&page| page != 1).count() as u64);
+
+    let remaining_pages: Vec<u64> = (2..=total_pages)
+        .filter(|page| !resumed.visited_pages.contains(page))
+        .collect();
 
-    for page in 2..=page1_results.total_pages {
-        log::info!("Checking dubbed anime page {}/{}...", page, page1_results.total_pages);
+    let listing_failed = Mutex::new(None);
 
-        let page_x_results = anisearch_client.get_dubbed_anime_list(page).unwrap();
-        process_dubbed_page(&mut dubbed_mal_ids, &mut anisearch_map, &page_x_results);
-        dubbed_anisearch_urls.extend(page_x_results.anisearch_urls.into_vec().into_iter());
+    concurrent::for_each(&remaining_pages, source.max_in_flight(), |_, &page| {
+        if checkpoint::interrupted() {
+            return;
+        }
+
+        log::info!("Checking dubbed anime page {}/{}...", page, total_pages);
+
+        let page_x_results = match source.get_dubbed_anime_list(page) {
+            Ok(results) => results,
+            Err(Error::Interrupted) => return,
+            Err(err) => {
+                log::error!("Failed to check dubbed anime page {}: {}", page, err);
+                listing_failed.lock().unwrap().get_or_insert(err.clone());
+                failures.lock().unwrap().push(err);
+                progress_bar.inc(1);
+                return;
+            }
+        };
+        checkpointer.record_page(source.name(), page, total_pages, &page_x_results.entries);
+        listed_anime.lock().unwrap().extend(page_x_results.entries.into_vec());
 
         progress_bar.inc(1);
-        std::thread::sleep(Duration::from_secs(1));
+    });
+
+    if checkpoint::interrupted() {
+        progress_bar.abandon();
+        return Err(Error::Interrupted);
+    }
+
+    // Without all listing pages, dubbed anime would be missing from the output
+    if let Some(err) = listing_failed.into_inner().unwrap() {
+        progress_bar.abandon();
+        return Err(err);
     }
 
-    // Save dubbed MyAnimeList ids as temporary result
-    let mut sorted_dubbed_mal_ids: Vec<u64> = dubbed_mal_ids.into_iter().collect();
-    sorted_dubbed_mal_ids.sort_unstable();
+    let listed_anime = listed_anime.into_inner().unwrap();
+    let mut source_map = match source.database_prefix() {
+        Some(prefix) => get_source_map(root, prefix),
+        None => match_source_map(root, &listed_anime),
+    };
+    apply_mappings(&mut source_map, root, config.mappings);
+    let mut unmatched = get_unmatched(source.name(), &source_map, &listed_anime);
+    let match_reviews = match config.fuzzy_matcher {
+        Some(fuzzy_matcher) => apply_fuzzy_matches(&mut source_map, fuzzy_matcher, &listed_anime, &mut unmatched),
+        None => Vec::new(),
+    };
+    let (mut dubbed_mal_ids, partial_matches) =
+        get_dubbed_mal_ids(&source_map, &listed_anime, config.validation_policy);
 
-    output::write_output(output_path, &sorted_dubbed_mal_ids, &[]);
+    // Check for incomplete dubs, but only of anime, which are in the offline database
+    let all_source_urls: HashSet<&str> = listed_anime
+        .iter()
+        .map(|listed| listed.url.as_str())
+        .filter(|url| source_map.contains_key(url))
+        .collect();
 
-    // Check for incomplete dubs
-    progress_bar.set_position(0);
-    progress_bar.set_length(dubbed_anisearch_urls.len() as u64);
+    // Complete dubs, which have been checked recently, are taken from the previous run
+    let now = chrono::Utc::now();
+    let previous_entries = &config.previous_state.entries;
+    let (dubbed_source_urls, reused_source_urls): (Vec<&str>, Vec<&str>) =
+        all_source_urls
+            .iter()
+            .partition(|&&url| match (config.recheck_after, previous_entries.get(url)) {
+                (Some(recheck_after), Some(entry_state)) => entry_state.needs_check(now, recheck_after),
+                _ => true,
+            });
 
-    for (index, dubbed_anisearch_url) in dubbed_anisearch_urls.iter().enumerate() {
+    if !reused_source_urls.is_empty() {
         log::info!(
-            "Checking if dub is complete {}/{}: {}",
-            index + 1,
-            dubbed_anisearch_urls.len(),
-            dubbed_anisearch_url
+            "Reusing the dub info of {} entries, which have been checked recently",
+            reused_source_urls.len()
         );
+    }
 
-        let mut add_to_incomplete_mal_ids = || {
-            if let Some(anime_entry_refcell) = anisearch_map.get(dubbed_anisearch_url.deref()) {
-                let mal_ids = &anime_entry_refcell.borrow().mal_ids;
-                dub_incomplete_mal_ids.extend(mal_ids.iter());
-            }
-        };
+    for url in reused_source_urls.iter() {
+        let dub_info = previous_entries[*url].dub_info.clone();
+        dub_statuses.lock().unwrap().insert(url, dub_info.status);
+        dub_infos.lock().unwrap().insert(url.to_string(), dub_info);
+    }
+
+    // Dubs, which have been checked before the previous run was aborted, count as checked by this run
+    let (resumed_source_urls, dubbed_source_urls): (Vec<&str>, Vec<&str>) = dubbed_source_urls
+        .into_iter()
+        .partition(|&url| resumed.entries.contains_key(url));
+
+    if !resumed_source_urls.is_empty() {
+        log::info!(
+            "Resuming with the dub info of {} entries from the checkpoint",
+            resumed_source_urls.len()
+        );
+    }
+
+    for url in resumed_source_urls {
+        let dub_info = resumed.entries[url].dub_info.clone();
+        dub_statuses.lock().unwrap().insert(url, dub_info.status);
+        dub_infos.lock().unwrap().insert(url.to_string(), dub_info);
+    }
+
+    progress_bar.set_position(0);
+    progress_bar.set_length(dubbed_source_urls.len() as u64);
 
-        match anisearch_client.get_dub_status(dubbed_anisearch_url) {
-            Ok(DubStatus::Complete) => {}
-            Ok(DubStatus::Incomplete | DubStatus::Upcoming) => {
-                // For now, I treat upcoming anime as incomplete
-                add_to_incomplete_mal_ids();
-                log::info!("Dub is incomplete: {}", dubbed_anisearch_url);
+    concurrent::for_each(
+        &dubbed_source_urls,
+        source.max_in_flight(),
+        |index, dubbed_source_url| {
+            if checkpoint::interrupted() {
+                return;
             }
-            Ok(DubStatus::NeverReleased) => {
-                if let Some(anime_entry_refcell) = anisearch_map.get(dubbed_anisearch_url.deref()) {
-                    let mal_ids = &anime_entry_refcell.borrow().mal_ids;
-                    dub_never_released_mal_ids.extend(mal_ids.iter());
-                    log::info!("Dub has never been released: {}", dubbed_anisearch_url);
+
+            log::info!(
+                "Checking if dub is complete {}/{}: {}",
+                index + 1,
+                dubbed_source_urls.len(),
+                dubbed_source_url
+            );
+
+            let set_dub_status = |status: DubStatus| {
+                dub_statuses.lock().unwrap().insert(dubbed_source_url, status);
+            };
+
+            let add_to_broken_links = |status: Option<u16>, redirects_to: Option<String>| {
+                if let Some(anime_entry_mutex) = source_map.get(*dubbed_source_url) {
+                    broken_links.lock().unwrap().push(BrokenLink {
+                        url: dubbed_source_url.to_string(),
+                        mal_ids: anime_entry_mutex.lock().unwrap().mal_ids.to_vec(),
+                        status,
+                        redirects_to,
+                    });
                 }
-            }
-            Err(_) => {
-                // I prefer to treat it as incomplete, if it cannot verify the completeness
-                // Happens with: https://anisearch.com/anime/18285
-                add_to_incomplete_mal_ids();
-                log::error!("Failed to check if the dub is complete for: {}", dubbed_anisearch_url);
-            }
-        };
+            };
 
-        progress_bar.inc(1);
-        std::thread::sleep(Duration::from_secs(1));
+            let dub_status = source.get_dub_info(dubbed_source_url).map(|dub_info| {
+                let status = dub_info.status;
+
+                if let Some(moved_to) = &dub_info.moved_to {
+                    log::warn!("Link has moved from {} to: {}", dubbed_source_url, moved_to);
+                    add_to_broken_links(None, Some(moved_to.clone()));
+                }
+
+                if let Some(previous_hash) = previous_entries
+                    .get(*dubbed_source_url)
+                    .and_then(|entry_state| entry_state.dub_info.content_hash.as_ref())
+                {
+                    if dub_info.content_hash.as_ref() != Some(previous_hash) {
+                        log::info!("Dub info has changed since the previous run: {}", dubbed_source_url);
+                    }
+                }
+
+                checkpointer.record_entry(
+                    source.name(),
+                    dubbed_source_url,
+                    &EntryState::new(dub_info.clone(), now),
+                );
+                dub_infos
+                    .lock()
+                    .unwrap()
+                    .insert(dubbed_source_url.to_string(), dub_info);
+                status
+            });
+
+            match dub_status {
+                Ok(status @ DubStatus::Complete) => set_dub_status(status),
+                Ok(status @ (DubStatus::Incomplete | DubStatus::Upcoming)) => {
+                    set_dub_status(status);
+                    log::info!("Dub is incomplete: {}", dubbed_source_url);
+                }
+                Ok(status @ DubStatus::NeverReleased) => {
+                    set_dub_status(status);
+                    log::info!("Dub has never been released: {}", dubbed_source_url);
+                }
+                // The entry is checked again, when the run is resumed
+                Err(Error::Interrupted) => {}
+                Err(err) => {
+                    if let Error::HttpStatus {
+                        status: status @ (404 | 410),
+                        ..
+                    } = err
+                    {
+                        add_to_broken_links(Some(status), None);
+                    }
+
+                    // I prefer to treat it as incomplete, if it cannot verify the completeness
+                    // Happens with: https://anisearch.com/anime/18285
+                    set_dub_status(DubStatus::Incomplete);
+                    log::error!("Failed to check if the dub is complete for: {}", err);
+                    failures.lock().unwrap().push(err);
+                }
+            };
+
+            progress_bar.inc(1);
+        },
+    );
+
+    if checkpoint::interrupted() {
+        progress_bar.abandon();
+        return Err(Error::Interrupted);
     }
 
-    // Remove never released dubs
-    for dub_never_released_mal_id in dub_never_released_mal_ids {
-        dub_incomplete_mal_ids.remove(&dub_never_released_mal_id);
-        sorted_dubbed_mal_ids.retain(|&mal_id| mal_id != dub_never_released_mal_id);
+    // A replay run is only meaningful if every page came from the archive,
+    // and if the source is down, most dubs would wrongly be marked as incomplete
+    if let Some(err) = failures
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|err| matches!(err, Error::ReplayMiss { .. } | Error::CircuitOpen { .. }))
+    {
+        progress_bar.abandon();
+        return Err(err.clone());
     }
 
-    // Save dubbed MyAnimeList ids, with incomplete information
-    let mut sorted_dub_incomplete_mal_ids: Vec<u64> = dub_incomplete_mal_ids.into_iter().collect();
-    sorted_dub_incomplete_mal_ids.sort_unstable();
+    let dub_statuses = aggregate_dub_statuses(
+        &source_map,
+        &dub_statuses.into_inner().unwrap(),
+        config.status_aggregation,
+    );
+    let mut dub_incomplete_mal_ids = BTreeSet::new();
+    let mut dub_upcoming_mal_ids = BTreeSet::new();
+    let mut dub_never_released_mal_ids = BTreeSet::new();
+
+    for (&mal_id, status) in dub_statuses.resolved.iter() {
+        match status {
+            DubStatus::Complete => {}
+            DubStatus::Incomplete => {
+                dub_incomplete_mal_ids.insert(mal_id);
+            }
+            // Upcoming dubs stay incomplete for the consumers, which do not know about upcoming ones
+            DubStatus::Upcoming => {
+                dub_incomplete_mal_ids.insert(mal_id);
+                dub_upcoming_mal_ids.insert(mal_id);
+            }
+            DubStatus::NeverReleased => {
+                dubbed_mal_ids.remove(&mal_id);
+                dub_never_released_mal_ids.insert(mal_id);
+            }
+        }
+    }
 
-    output::write_output(output_path, &sorted_dubbed_mal_ids, &sorted_dub_incomplete_mal_ids);
+    let dub_infos = dub_infos.into_inner().unwrap();
+
+    // Failed checks count as incomplete, but their titles are also reported as unknown
+    let dub_unknown_mal_ids = dubbed_source_urls
+        .iter()
+        .filter(|&&url| !dub_infos.contains_key(url))
+        .filter_map(|url| source_map.get(url))
+        .flat_map(|anime_entry_mutex| anime_entry_mutex.lock().unwrap().mal_ids.to_vec())
+        .filter(|mal_id| dub_incomplete_mal_ids.contains(mal_id))
+        .collect();
+
+    // Failed checks keep the state of the previous run, so they are checked again next time
+    let state = all_source_urls
+        .iter()
+        .filter_map(|&url| {
+            let entry_state = match (dub_infos.get(url), resumed.entries.get(url)) {
+                // Entries, which have been checked before the previous run was aborted, keep the time of that check
+                (Some(_), Some(entry_state)) => entry_state.clone(),
+                (Some(dub_info), None) if !reused_source_urls.contains(&url) => EntryState::new(dub_info.clone(), now),
+                _ => previous_entries.get(url)?.clone(),
+            };
+
+            Some((url.to_string(), entry_state))
+        })
+        .collect();
+    let mut broken_links = broken_links.into_inner().unwrap();
+    broken_links.sort_by(|a, b| a.url.cmp(&b.url));
+    let progress = get_dub_progress(source.name(), &source_map, &dub_infos);
+    let companies = get_companies(&source_map, &dub_infos);
+    let provenance = dubbed_mal_ids
+        .iter()
+        .map(|&mal_id| (mal_id, BTreeSet::from([source.name()])))
+        .collect();
 
     // Clean up
     progress_bar.finish();
     multi.remove(&progress_bar);
+
+    Ok(SourceResult {
+        dubbed: dubbed_mal_ids,
+        incomplete: dub_incomplete_mal_ids,
+        upcoming: dub_upcoming_mal_ids,
+        never_released: dub_never_released_mal_ids,
+        unknown: dub_unknown_mal_ids,
+        progress,
+        companies,
+        provenance,
+        unmatched,
+        broken_links,
+        partial_matches,
+        status_conflicts: dub_statuses.conflicts,
+        match_reviews,
+        state,
+    })
 }
 
-fn process_dubbed_page(
-    dubbed_mal_ids: &mut HashSet<u64>,
-    anisearch_map: &mut HashMap<&str, Rc<RefCell<AnimeEntry>>>,
-    dubbed_anime: &DubbedAnime,
-) {
-    for anisearch_url in dubbed_anime.anisearch_urls.iter() {
-        let Some(anime_entry_refcell) = anisearch_map.get_mut(anisearch_url.deref()) else {
+/// Dub statuses of the MyAnimeList ids of a source.
+struct AggregatedStatuses {
+    resolved: BTreeMap<u64, DubStatus>,
+    /// MyAnimeList ids, whose linked entries have different statuses
+    conflicts: Vec<StatusConflict>,
+}
+
+/// Combines the dub statuses of all entries of the source, which are linked to the same MyAnimeList id.
+fn aggregate_dub_statuses(
+    source_map: &HashMap<&str, Arc<Mutex<AnimeEntry>>>,
+    dub_statuses: &HashMap<&str, DubStatus>,
+    status_aggregation: cli::StatusAggregation,
+) -> AggregatedStatuses {
+    let mut statuses_by_mal_id: BTreeMap<u64, BTreeMap<String, DubStatus>> = BTreeMap::new();
+
+    for (&source_url, &status) in dub_statuses.iter() {
+        let Some(anime_entry_mutex) = source_map.get(source_url) else {
+            continue;
+        };
+
+        for &mal_id in anime_entry_mutex.lock().unwrap().mal_ids.iter() {
+            statuses_by_mal_id
+                .entry(mal_id)
+                .or_default()
+                .insert(source_url.to_string(), status);
+        }
+    }
+
+    let mut aggregated = AggregatedStatuses {
+        resolved: BTreeMap::new(),
+        conflicts: Vec::new(),
+    };
+
+    for (mal_id, statuses) in statuses_by_mal_id {
+        let Some(resolved) = status_aggregation.resolve(statuses.values().copied()) else {
             continue;
         };
-        let mut anime_entry = anime_entry_refcell.borrow_mut();
 
-        anime_entry.current_validations += 1;
+        aggregated.resolved.insert(mal_id, resolved);
+
+        if statuses.values().any(|&status| status != resolved) {
+            log::warn!(
+                "Linked entries of https://myanimelist.net/anime/{} have different dub statuses, using {:?}: {:?}",
+                mal_id,
+                resolved,
+                statuses
+            );
+            aggregated.conflicts.push(StatusConflict {
+                mal_id,
+                statuses,
+                resolved,
+            });
+        }
+    }
+
+    aggregated
+}
+
+/// Compares the dubbed episodes of a source with the episodes in the offline database.
+///
+/// Returns the dub progress in percent for every MyAnimeList id, for which all entries of the source list
+/// their dubbed episodes. Mismatches between the dub status and the episode count are logged.
+fn get_dub_progress(
+    source_name: &str,
+    source_map: &HashMap<&str, Arc<Mutex<AnimeEntry>>>,
+    dub_infos: &HashMap<String, DubInfo>,
+) -> BTreeMap<u64, u8> {
+    struct EpisodeCount<'a> {
+        anime_entry: &'a Arc<Mutex<AnimeEntry>>,
+        dubbed: u32,
+        /// Number of entries of the source, which list their dubbed episodes
+        reported: u64,
+    }
+
+    let mut episode_counts: HashMap<*const Mutex<AnimeEntry>, EpisodeCount> = HashMap::new();
+    let mut mismatches: u64 = 0;
+
+    for (source_url, dub_info) in dub_infos {
+        let (Some(anime_entry_mutex), Some(episodes)) = (source_map.get(source_url.deref()), dub_info.episodes) else {
+            continue;
+        };
+        let anime_entry = anime_entry_mutex.lock().unwrap();
+
+        // With several entries of the source, the offline database episodes are spread across them
+        if anime_entry.validations_required == 1 && anime_entry.episodes > 0 {
+            let all_dubbed = episodes.dubbed >= anime_entry.episodes;
+
+            if dub_info.status == DubStatus::Complete && !all_dubbed {
+                mismatches += 1;
+                log::warn!(
+                    "Dub is completed according to {}, but only {}/{} episodes are dubbed: {}",
+                    source_name,
+                    episodes.dubbed,
+                    anime_entry.episodes,
+                    source_url
+                );
+            } else if dub_info.status != DubStatus::Complete && all_dubbed {
+                mismatches += 1;
+                log::warn!(
+                    "Dub is not completed according to {}, but all {} episodes are dubbed: {}",
+                    source_name,
+                    anime_entry.episodes,
+                    source_url
+                );
+            }
+        }
+
+        let episode_count = episode_counts
+            .entry(Arc::as_ptr(anime_entry_mutex))
+            .or_insert(EpisodeCount {
+                anime_entry: anime_entry_mutex,
+                dubbed: 0,
+                reported: 0,
+            });
+        episode_count.dubbed += episodes.dubbed;
+        episode_count.reported += 1;
+    }
+
+    if mismatches > 0 {
+        log::warn!(
+            "Found {} mismatch(es) between dub status and dubbed episodes",
+            mismatches
+        );
+    }
+
+    let mut progress = BTreeMap::new();
+
+    for episode_count in episode_counts.into_values() {
+        let anime_entry = episode_count.anime_entry.lock().unwrap();
+
+        if episode_count.reported != anime_entry.validations_required || anime_entry.episodes == 0 {
+            continue;
+        }
+
+        let percentage = (episode_count.dubbed as u64 * 100 / anime_entry.episodes as u64).min(100) as u8;
+
+        for &mal_id in anime_entry.mal_ids.iter() {
+            progress.insert(mal_id, percentage);
+        }
+    }
+
+    progress
+}
+
+/// Groups the MyAnimeList ids by the companies, which licensed, published or dubbed them.
+fn get_companies(
+    source_map: &HashMap<&str, Arc<Mutex<AnimeEntry>>>,
+    dub_infos: &HashMap<String, DubInfo>,
+) -> BTreeMap<String, output::CompanyTitles> {
+    let mut companies: BTreeMap<String, output::CompanyTitles> = BTreeMap::new();
+
+    for (source_url, dub_info) in dub_infos {
+        let Some(anime_entry_mutex) = source_map.get(source_url.deref()) else {
+            continue;
+        };
+        let mal_ids = &anime_entry_mutex.lock().unwrap().mal_ids;
+
+        for licensor in dub_info.licensors.iter() {
+            companies
+                .entry(licensor.clone())
+                .or_default()
+                .licensor
+                .extend(mal_ids.iter());
+        }
+        for publisher in dub_info.publishers.iter() {
+            companies
+                .entry(publisher.clone())
+                .or_default()
+                .publisher
+                .extend(mal_ids.iter());
+        }
+        for dub_studio in dub_info.dub_studios.iter() {
+            companies
+                .entry(dub_studio.clone())
+                .or_default()
+                .dub_studio
+                .extend(mal_ids.iter());
+        }
+    }
+
+    companies
+}
+
+fn log_failure_summary(failures: &[Error]) {
+    if failures.is_empty() {
+        return;
+    }
+
+    let mut failures_by_kind: HashMap<&str, Vec<&Error>> = HashMap::new();
+
+    for failure in failures {
+        failures_by_kind.entry(failure.kind()).or_default().push(failure);
+    }
+
+    let mut kinds: Vec<&str> = failures_by_kind.keys().copied().collect();
+    kinds.sort_unstable();
+
+    log::warn!("{} request(s) failed during this run:", failures.len());
+
+    for kind in kinds {
+        let kind_failures = &failures_by_kind[kind];
+        log::warn!("{} ({}):", kind, kind_failures.len());
+
+        for failure in kind_failures {
+            log::warn!("  {}", failure);
+        }
+    }
+}
+
+/// Returns the MyAnimeList ids of the listed anime, for which enough entries of the source are listed
+/// according to the policy, and the titles, for which only some of their entries are listed.
+fn get_dubbed_mal_ids(
+    source_map: &HashMap<&str, Arc<Mutex<AnimeEntry>>>,
+    listed_anime: &[ListedAnime],
+    validation_policy: cli::ValidationPolicy,
+) -> (BTreeSet<u64>, Vec<PartialMatch>) {
+    let listed_urls: HashSet<&str> = listed_anime.iter().map(|listed| listed.url.as_str()).collect();
+
+    for listed_url in listed_urls.iter() {
+        if let Some(anime_entry_mutex) = source_map.get(listed_url) {
+            anime_entry_mutex.lock().unwrap().current_validations += 1;
+        }
+    }
+
+    // Several URLs of the source can be linked to the same entry
+    struct LinkedUrls<'a> {
+        anime_entry: &'a Arc<Mutex<AnimeEntry>>,
+        source_urls: Vec<&'a str>,
+    }
+
+    let mut linked_urls: HashMap<*const Mutex<AnimeEntry>, LinkedUrls> = HashMap::new();
+
+    for (source_url, anime_entry_mutex) in source_map.iter() {
+        linked_urls
+            .entry(Arc::as_ptr(anime_entry_mutex))
+            .or_insert_with(|| LinkedUrls {
+                anime_entry: anime_entry_mutex,
+                source_urls: Vec::new(),
+            })
+            .source_urls
+            .push(source_url);
+    }
+
+    let mut dubbed_mal_ids = BTreeSet::new();
+    let mut partial_matches = Vec::new();
+
+    for LinkedUrls {
+        anime_entry,
+        mut source_urls,
+    } in linked_urls.into_values()
+    {
+        let anime_entry = anime_entry.lock().unwrap();
+
+        if anime_entry.current_validations == 0 {
+            continue;
+        }
+
+        let dubbed = validation_policy.is_satisfied(anime_entry.current_validations, anime_entry.validations_required);
 
-        if anime_entry.current_validations == anime_entry.validations_required {
+        if dubbed {
             dubbed_mal_ids.extend(anime_entry.mal_ids.iter());
         }
+
+        if anime_entry.current_validations < anime_entry.validations_required {
+            source_urls.sort_unstable();
+            let (listed, unlisted): (Vec<&str>, Vec<&str>) = source_urls
+                .iter()
+                .partition(|source_url| listed_urls.contains(*source_url));
+
+            log::info!(
+                "Only {} of {} entries are listed as dubbed for MyAnimeList ids {:?}: {}",
+                listed.len(),
+                source_urls.len(),
+                anime_entry.mal_ids,
+                listed.join(", ")
+            );
+
+            partial_matches.push(PartialMatch {
+                mal_ids: anime_entry.mal_ids.to_vec(),
+                listed: listed.into_iter().map(str::to_string).collect(),
+                unlisted: unlisted.into_iter().map(str::to_string).collect(),
+                dubbed,
+            });
+        }
     }
+
+    partial_matches.sort_by(|a, b| a.mal_ids.cmp(&b.mal_ids));
+
+    (dubbed_mal_ids, partial_matches)
+}
+
+/// Collects the listed anime, which could not be mapped to any MyAnimeList id.
+fn get_unmatched(
+    source_name: &'static str,
+    source_map: &HashMap<&str, Arc<Mutex<AnimeEntry>>>,
+    listed_anime: &[ListedAnime],
+) -> Vec<UnmatchedAnime> {
+    let mut unmatched: Vec<UnmatchedAnime> = Vec::new();
+
+    for listed in listed_anime {
+        if source_map.contains_key(listed.url.as_str()) {
+            continue;
+        }
+
+        log::debug!("Dubbed anime has no MyAnimeList id: {}", listed.url);
+        unmatched.push(UnmatchedAnime {
+            source: source_name,
+            url: listed.url.clone(),
+            title: listed.title.clone(),
+            year: listed.year,
+            r#type: listed.r#type.clone(),
+        });
+    }
+
+    // An anime may be listed on more than one page
+    unmatched.sort_by(|a, b| a.url.cmp(&b.url));
+    unmatched.dedup_by(|a, b| a.url == b.url);
+    unmatched
+}
+
+/// Links unmatched anime to the best fuzzy match, if it is certain enough.
+///
+/// Returns the candidates of the other unmatched anime for review.
+fn apply_fuzzy_matches<'a>(
+    source_map: &mut HashMap<&'a str, Arc<Mutex<AnimeEntry>>>,
+    fuzzy_matcher: &FuzzyMatcher,
+    listed_anime: &'a [ListedAnime],
+    unmatched: &mut Vec<UnmatchedAnime>,
+) -> Vec<MatchReview> {
+    let mut match_reviews = Vec::new();
+
+    unmatched.retain(|unmatched_anime| {
+        let Some(listed) = listed_anime.iter().find(|listed| listed.url == unmatched_anime.url) else {
+            return true;
+        };
+        let titles = std::iter::once(&listed.title).chain(listed.synonyms.iter());
+        let candidates: Vec<_> = fuzzy_matcher
+            .candidates(titles.map(String::as_str), listed.year, listed.r#type.as_deref(), 5)
+            .into_iter()
+            .filter(|candidate| get_mal_ids(candidate.anime).is_some())
+            .collect();
+
+        if let Some(best) = fuzzy_matcher.auto_apply(&candidates) {
+            let mal_ids = get_mal_ids(best.anime).unwrap();
+            log::info!(
+                "Fuzzy match links {} to MyAnimeList ids {:?} with a score of {:.2}",
+                listed.url,
+                mal_ids,
+                best.score
+            );
+
+            let anime_entry = AnimeEntry {
+                mal_ids,
+                episodes: best.anime.episodes,
+                validations_required: 1,
+                current_validations: 0,
+            };
+            source_map.insert(listed.url.as_str(), Arc::new(Mutex::new(anime_entry)));
+            return false;
+        }
+
+        if !candidates.is_empty() {
+            match_reviews.push(MatchReview {
+                anime: unmatched_anime.clone(),
+                candidates: candidates
+                    .iter()
+                    .map(|candidate| MatchCandidate {
+                        mal_ids: get_mal_ids(candidate.anime).unwrap_or_default().into_vec(),
+                        title: candidate.anime.title.clone(),
+                        year: candidate.anime.anime_season.year,
+                        r#type: candidate.anime.r#type,
+                        score: (candidate.score * 100.0).round() / 100.0,
+                    })
+                    .collect(),
+            });
+        }
+
+        true
+    });
+
+    if !match_reviews.is_empty() {
+        log::info!("{} fuzzy match(es) need to be reviewed", match_reviews.len());
+    }
+
+    match_reviews
 }
 
 struct AnimeEntry {
     mal_ids: Box<[u64]>,
+    /// Number of episodes according to the offline database
+    episodes: u32,
     validations_required: u64,
     current_validations: u64,
 }
 
-fn get_anisearch_map<'a>(root: &'a Root) -> HashMap<&'a str, Rc<RefCell<AnimeEntry>>> {
-    let mut anisearch_map: HashMap<&'a str, Rc<RefCell<AnimeEntry>>> = HashMap::with_capacity(root.data.len());
+/// Maps the offline database URLs of a source, which start with `prefix`, to their MyAnimeList ids.
+fn get_source_map<'a>(root: &'a Root, prefix: &str) -> HashMap<&'a str, Arc<Mutex<AnimeEntry>>> {
+    let mut source_map: HashMap<&'a str, Arc<Mutex<AnimeEntry>>> = HashMap::with_capacity(root.data.len());
 
     for anime in root.data.iter() {
-        let mal_urls: Box<[&str]> = anime
+        let Some(mal_ids) = get_mal_ids(anime) else {
+            continue;
+        };
+
+        let source_urls: Box<[&str]> = anime
             .sources
             .iter()
-            .filter(|&src| src.starts_with("https://myanimelist.net/"))
+            .filter(|&src| src.starts_with(prefix))
             .map(|src| src.deref())
             .collect();
 
-        if mal_urls.is_empty() {
-            continue;
-        }
+        let anime_entry = Arc::new(Mutex::new(AnimeEntry {
+            mal_ids,
+            episodes: anime.episodes,
+            validations_required: source_urls.len() as u64,
+            current_validations: 0,
+        }));
 
-        let mal_ids: Box<[u64]> = mal_urls
-            .iter()
-            .filter_map(|&mal_url| {
-                let id = mal_parse_id(mal_url);
+        for source_url in source_urls.iter() {
+            source_map.insert(source_url, anime_entry.clone());
+        }
+    }
 
-                if id.is_none() {
-                    log::warn!("Failed to parse id from MyAnimeList URL: {}", mal_url);
-                }
+    source_map
+}
 
-                id
-            })
-            .collect();
+/// Maps the URLs of listed anime to their MyAnimeList ids by matching their titles and years,
+/// for sources which the offline database does not link.
+fn match_source_map<'a>(root: &'a Root, listed_anime: &'a [ListedAnime]) -> HashMap<&'a str, Arc<Mutex<AnimeEntry>>> {
+    let title_index = matching::TitleIndex::new(root);
+    let mut source_map: HashMap<&'a str, Arc<Mutex<AnimeEntry>>> = HashMap::with_capacity(listed_anime.len());
+    let mut unmatched: u64 = 0;
 
-        if mal_urls.len() != mal_ids.len() {
+    for listed in listed_anime {
+        if source_map.contains_key(listed.url.as_str()) {
             continue;
         }
 
-        let anisearch_urls: Box<[&str]> = anime
-            .sources
-            .iter()
-            .filter(|&src| src.starts_with("https://anisearch.com/"))
-            .map(|src| src.deref())
-            .collect();
+        let titles = std::iter::once(&listed.title).chain(listed.synonyms.iter());
+        let Some(anime) = title_index.find(titles.map(|title| title.as_str()), listed.year) else {
+            unmatched += 1;
+            log::debug!(
+                "Could not match to the offline database: {} ({})",
+                listed.title,
+                listed.url
+            );
+            continue;
+        };
+        let Some(mal_ids) = get_mal_ids(anime) else {
+            continue;
+        };
 
-        let anime_entry = Rc::new(RefCell::new(AnimeEntry {
+        // Every listed production stands for itself
+        let anime_entry = AnimeEntry {
             mal_ids,
-            validations_required: anisearch_urls.len() as u64,
+            episodes: anime.episodes,
+            validations_required: 1,
             current_validations: 0,
-        }));
+        };
+        source_map.insert(&listed.url, Arc::new(Mutex::new(anime_entry)));
+    }
+
+    if unmatched > 0 {
+        log::info!("Could not match {} listed title(s) to the offline database", unmatched);
+    }
+
+    source_map
+}
+
+/// Applies manual mappings to the source map. Every changed entry is detached from the other entries
+/// of its offline database entry, so that it is validated on its own.
+fn apply_mappings<'a>(
+    source_map: &mut HashMap<&'a str, Arc<Mutex<AnimeEntry>>>,
+    root: &'a Root,
+    mappings: &[MappingOverride],
+) {
+    for mapping in mappings {
+        let anisearch_url = mapping.anisearch_url();
+        // Reading the mappings makes sure that only one offline database entry links the aniSearch entry
+        let Some((anime, source_url)) = root.data.iter().find_map(|anime| {
+            anime
+                .sources
+                .iter()
+                .find(|&src| *src == anisearch_url)
+                .map(|src| (anime, src.as_str()))
+        }) else {
+            continue;
+        };
 
-        for anisearch_url in anisearch_urls.iter() {
-            anisearch_map.insert(anisearch_url, anime_entry.clone());
+        let previous = source_map.remove(source_url);
+        let mut mal_ids: Vec<u64> = match &previous {
+            Some(anime_entry_mutex) => {
+                let mut anime_entry = anime_entry_mutex.lock().unwrap();
+                anime_entry.validations_required -= 1;
+                anime_entry.mal_ids.to_vec()
+            }
+            None => Vec::new(),
+        };
+
+        match mapping.action {
+            MappingAction::Add => {
+                for &mal_id in mapping.mal.iter() {
+                    if !mal_ids.contains(&mal_id) {
+                        mal_ids.push(mal_id);
+                    }
+                }
+            }
+            MappingAction::Remove => mal_ids.retain(|mal_id| !mapping.mal.contains(mal_id)),
+            MappingAction::Replace => mal_ids = mapping.mal.to_vec(),
+        }
+
+        if mal_ids.is_empty() {
+            log::info!("Mapping unlinks {} from MyAnimeList", anisearch_url);
+            continue;
         }
+
+        log::info!("Mapping links {} to MyAnimeList ids {:?}", anisearch_url, mal_ids);
+
+        let anime_entry = AnimeEntry {
+            mal_ids: mal_ids.into_boxed_slice(),
+            episodes: anime.episodes,
+            validations_required: 1,
+            current_validations: 0,
+        };
+        source_map.insert(source_url, Arc::new(Mutex::new(anime_entry)));
     }
+}
+
+/// Parses the MyAnimeList ids of an anime. Returns `None`, if it has none or one could not be parsed.
+fn get_mal_ids(anime: &Anime) -> Option<Box<[u64]>> {
+    let mal_urls: Box<[&str]> = anime
+        .sources
+        .iter()
+        .filter(|&src| src.starts_with("https://myanimelist.net/"))
+        .map(|src| src.deref())
+        .collect();
+
+    if mal_urls.is_empty() {
+        return None;
+    }
+
+    let mal_ids: Box<[u64]> = mal_urls
+        .iter()
+        .filter_map(|&mal_url| {
+            let id = mal_parse_id(mal_url);
+
+            if id.is_none() {
+                log::warn!("Failed to parse id from MyAnimeList URL: {}", mal_url);
+            }
+
+            id
+        })
+        .collect();
 
-    anisearch_map
+    (mal_urls.len() == mal_ids.len()).then_some(mal_ids)
 }
 
 fn mal_parse_id(anime_url: &str) -> Option<u64> {
@@ -226,7 +1535,84 @@ fn mal_parse_id(anime_url: &str) -> Option<u64> {
 
 #[cfg(test)]
 mod tests {
-    use super::mal_parse_id;
+    use std::collections::{BTreeSet, HashMap};
+    use std::sync::{Arc, Mutex};
+
+    use super::{aggregate_dub_statuses, get_dubbed_mal_ids, mal_parse_id, AnimeEntry};
+    use crate::cli::{StatusAggregation, ValidationPolicy};
+    use crate::source::{DubStatus, ListedAnime};
+
+    #[test]
+    fn test_get_dubbed_mal_ids() {
+        let listed = |url: &str| ListedAnime {
+            url: url.to_string(),
+            title: String::new(),
+            synonyms: Vec::new(),
+            year: None,
+            r#type: None,
+        };
+        // Two of the three entries linked to MyAnimeList 1 are listed, one is listed twice
+        let listed_anime = [listed("a"), listed("b"), listed("b"), listed("x")];
+
+        for (validation_policy, expected) in [
+            (ValidationPolicy::All, BTreeSet::new()),
+            (ValidationPolicy::Any, BTreeSet::from([1])),
+            (ValidationPolicy::Majority, BTreeSet::from([1])),
+        ] {
+            let anime_entry = Arc::new(Mutex::new(AnimeEntry {
+                mal_ids: Box::from([1]),
+                episodes: 12,
+                validations_required: 3,
+                current_validations: 0,
+            }));
+            let source_map = HashMap::from([
+                ("a", anime_entry.clone()),
+                ("b", anime_entry.clone()),
+                ("c", anime_entry),
+            ]);
+
+            let (dubbed_mal_ids, partial_matches) = get_dubbed_mal_ids(&source_map, &listed_anime, validation_policy);
+
+            assert_eq!(dubbed_mal_ids, expected);
+            assert_eq!(partial_matches.len(), 1);
+            assert_eq!(partial_matches[0].listed, ["a", "b"]);
+            assert_eq!(partial_matches[0].unlisted, ["c"]);
+            assert_eq!(partial_matches[0].dubbed, !expected.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_aggregate_dub_statuses() {
+        let anime_entry = |mal_id| {
+            Arc::new(Mutex::new(AnimeEntry {
+                mal_ids: Box::from([mal_id]),
+                episodes: 12,
+                validations_required: 2,
+                current_validations: 2,
+            }))
+        };
+        let (first, second) = (anime_entry(1), anime_entry(2));
+        let source_map = HashMap::from([("a", first.clone()), ("b", first), ("c", second.clone()), ("d", second)]);
+        let dub_statuses = HashMap::from([
+            ("a", DubStatus::Complete),
+            ("b", DubStatus::NeverReleased),
+            ("c", DubStatus::Incomplete),
+            ("d", DubStatus::Incomplete),
+        ]);
+
+        let best = aggregate_dub_statuses(&source_map, &dub_statuses, StatusAggregation::BestWins);
+        assert_eq!(best.resolved[&1], DubStatus::Complete);
+        assert_eq!(best.resolved[&2], DubStatus::Incomplete);
+
+        let worst = aggregate_dub_statuses(&source_map, &dub_statuses, StatusAggregation::WorstWins);
+        assert_eq!(worst.resolved[&1], DubStatus::NeverReleased);
+        assert_eq!(worst.resolved[&2], DubStatus::Incomplete);
+
+        // Only the entries of MyAnimeList 1 disagree
+        assert_eq!(worst.conflicts.len(), 1);
+        assert_eq!(worst.conflicts[0].mal_id, 1);
+        assert_eq!(worst.conflicts[0].statuses.len(), 2);
+    }
 
     #[test]
     fn test_parse_mal_id() {
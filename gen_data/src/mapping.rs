@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::anisearch::ANIME_URL_PREFIX;
+use crate::database::Root;
+use crate::error::{Error, Result};
+
+/// Manual correction of the MyAnimeList ids, which an aniSearch entry is linked to.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MappingOverride {
+    pub anisearch: u64,
+    pub action: MappingAction,
+    pub mal: Box<[u64]>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MappingAction {
+    /// Links the MyAnimeList ids in addition to the ones from the offline database
+    Add,
+    /// Unlinks the MyAnimeList ids
+    Remove,
+    /// Links only the given MyAnimeList ids
+    Replace,
+}
+
+#[derive(Deserialize)]
+struct MappingFile {
+    #[serde(default, rename = "mapping")]
+    mappings: Vec<MappingOverride>,
+}
+
+impl MappingOverride {
+    pub fn anisearch_url(&self) -> String {
+        format!("{}{}", ANIME_URL_PREFIX, self.anisearch)
+    }
+}
+
+/// Reads mapping overrides from a TOML file with one `[[mapping]]` table per aniSearch entry:
+///
+/// ```toml
+/// [[mapping]]
+/// anisearch = 14
+/// action = "replace"
+/// mal = [235]
+/// ```
+///
+/// Every referenced id has to exist in the offline database, and the aniSearch entry
+/// has to be linked from exactly one of its entries.
+pub fn read_mappings(path: &Path, root: &Root) -> Result<Vec<MappingOverride>> {
+    let text = std::fs::read_to_string(path).map_err(|err| Error::Io {
+        path: path.display().to_string(),
+        message: err.to_string(),
+    })?;
+
+    parse(&text, root).map_err(|message| Error::InvalidFile {
+        path: path.display().to_string(),
+        message,
+    })
+}
+
+fn parse(text: &str, root: &Root) -> std::result::Result<Vec<MappingOverride>, String> {
+    let file: MappingFile = toml::from_str(text).map_err(|err| err.message().to_string())?;
+
+    // Number of offline database entries per source URL
+    let mut sources: HashMap<&str, usize> = HashMap::new();
+
+    for source in root.data.iter().flat_map(|anime| anime.sources.iter()) {
+        *sources.entry(source.as_str()).or_default() += 1;
+    }
+
+    for mapping in file.mappings.iter() {
+        let anisearch_url = mapping.anisearch_url();
+
+        match sources.get(anisearch_url.as_str()) {
+            None => return Err(format!("{} is not in the offline database", anisearch_url)),
+            // The mapping could only be applied to one of the entries
+            Some(&count) if count > 1 => {
+                return Err(format!(
+                    "{} is in {} entries of the offline database and cannot be mapped",
+                    anisearch_url, count
+                ))
+            }
+            Some(_) => {}
+        }
+
+        if mapping.mal.is_empty() && mapping.action != MappingAction::Replace {
+            return Err(format!("mapping for {} has no MyAnimeList ids", anisearch_url));
+        }
+
+        for mal_id in mapping.mal.iter() {
+            let mal_url = format!("https://myanimelist.net/anime/{mal_id}");
+
+            if !sources.contains_key(mal_url.as_str()) {
+                return Err(format!("{} is not in the offline database", mal_url));
+            }
+        }
+    }
+
+    Ok(file.mappings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, MappingAction, MappingOverride};
+    use crate::database::Root;
+
+    fn root() -> Root {
+        serde_json::from_str(
+            r#"{
+              "license": { "name": "", "url": "" },
+              "repository": "",
+              "lastUpdate": "",
+              "data": [
+                {
+                  "sources": ["https://anisearch.com/anime/14", "https://myanimelist.net/anime/235"],
+                  "title": "Meitantei Conan",
+                  "type": "TV", "episodes": 0, "status": "ONGOING",
+                  "animeSeason": { "season": "WINTER", "year": 1996 },
+                  "picture": "", "thumbnail": "",
+                  "synonyms": [], "relatedAnime": [], "tags": []
+                },
+                {
+                  "sources": ["https://anisearch.com/anime/1", "https://myanimelist.net/anime/1"],
+                  "title": "Cowboy Bebop",
+                  "type": "TV", "episodes": 26, "status": "FINISHED",
+                  "animeSeason": { "season": "SPRING", "year": 1998 },
+                  "picture": "", "thumbnail": "",
+                  "synonyms": [], "relatedAnime": [], "tags": []
+                },
+                {
+                  "sources": ["https://anisearch.com/anime/1", "https://myanimelist.net/anime/5"],
+                  "title": "Cowboy Bebop: Tengoku no Tobira",
+                  "type": "MOVIE", "episodes": 1, "status": "FINISHED",
+                  "animeSeason": { "season": "SUMMER", "year": 2001 },
+                  "picture": "", "thumbnail": "",
+                  "synonyms": [], "relatedAnime": [], "tags": []
+                }
+              ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let root = root();
+
+        assert_eq!(
+            parse(
+                "[[mapping]]\nanisearch = 14\naction = \"replace\"\nmal = [235]\n",
+                &root
+            ),
+            Ok(vec![MappingOverride {
+                anisearch: 14,
+                action: MappingAction::Replace,
+                mal: Box::from([235]),
+            }])
+        );
+        assert_eq!(
+            parse("[[mapping]]\nanisearch = 14\naction = \"add\"\nmal = [2]\n", &root),
+            Err("https://myanimelist.net/anime/2 is not in the offline database".to_string())
+        );
+        assert_eq!(
+            parse("[[mapping]]\nanisearch = 2\naction = \"remove\"\nmal = [235]\n", &root),
+            Err("https://anisearch.com/anime/2 is not in the offline database".to_string())
+        );
+        // A mapping of an aniSearch entry, which is linked from several offline database entries, is ambiguous
+        assert_eq!(
+            parse("[[mapping]]\nanisearch = 1\naction = \"replace\"\nmal = [1]\n", &root),
+            Err(
+                "https://anisearch.com/anime/1 is in 2 entries of the offline database and cannot be mapped"
+                    .to_string()
+            )
+        );
+    }
+}
@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+
+use crate::database::{Anime, Root, Type};
+
+/// Normalizes a title for comparison, e.g. `Kimi no Na wa.` becomes `kimi no na wa`.
+pub fn normalize_title(title: &str) -> String {
+    title
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Finds anime of the offline database by their title or synonyms.
+pub struct TitleIndex<'a> {
+    titles: HashMap<String, Vec<&'a Anime>>,
+}
+
+impl<'a> TitleIndex<'a> {
+    pub fn new(root: &'a Root) -> Self {
+        let mut titles: HashMap<String, Vec<&'a Anime>> = HashMap::new();
+
+        for anime in root.data.iter() {
+            for title in std::iter::once(&anime.title).chain(anime.synonyms.iter()) {
+                let candidates = titles.entry(normalize_title(title)).or_default();
+
+                if !candidates.iter().any(|&candidate| std::ptr::eq(candidate, anime)) {
+                    candidates.push(anime);
+                }
+            }
+        }
+
+        Self { titles }
+    }
+
+    /// Returns the only anime, which has one of the titles and was released around the year.
+    ///
+    /// Returns `None`, if no anime or more than one anime matches.
+    pub fn find<'t>(&self, titles: impl IntoIterator<Item = &'t str>, year: Option<u32>) -> Option<&'a Anime> {
+        let mut matches: Vec<&'a Anime> = Vec::new();
+
+        for title in titles {
+            let Some(candidates) = self.titles.get(&normalize_title(title)) else {
+                continue;
+            };
+
+            for &candidate in candidates {
+                // The dub may be listed with the year of the first episode or of a later season
+                let year_matches = match (year, candidate.anime_season.year) {
+                    (Some(year), Some(candidate_year)) => year.abs_diff(candidate_year) <= 1,
+                    _ => true,
+                };
+
+                if year_matches && !matches.iter().any(|&other| std::ptr::eq(other, candidate)) {
+                    matches.push(candidate);
+                }
+            }
+        }
+
+        match matches.as_slice() {
+            [anime] => Some(anime),
+            _ => None,
+        }
+    }
+}
+
+/// An anime of the offline database, which may be the one listed by a source.
+#[derive(Debug, Clone, Copy)]
+pub struct Candidate<'a> {
+    pub anime: &'a Anime,
+    /// Between 0 and 1, where 1 means that title, year and type are the same
+    pub score: f64,
+}
+
+/// Suggests anime of the offline database for titles, which could not be matched exactly.
+///
+/// The score weights the similarity of the titles with 70 %, the year with 20 % and the type with 10 %.
+pub struct FuzzyMatcher<'a> {
+    titles: Vec<(Vec<[char; 2]>, &'a Anime)>,
+    /// Minimum score of a candidate to be applied without review
+    auto_apply_score: Option<f64>,
+}
+
+impl<'a> FuzzyMatcher<'a> {
+    /// Titles, which are less similar than this, do not make an anime a candidate.
+    const MIN_TITLE_SIMILARITY: f64 = 0.5;
+
+    pub fn new(root: &'a Root, auto_apply_score: Option<f64>) -> Self {
+        let titles = root
+            .data
+            .iter()
+            .flat_map(|anime| {
+                std::iter::once(&anime.title)
+                    .chain(anime.synonyms.iter())
+                    .map(move |title| (bigrams(&normalize_title(title)), anime))
+            })
+            .collect();
+
+        Self {
+            titles,
+            auto_apply_score,
+        }
+    }
+
+    /// Returns up to `limit` candidates for an anime with the given titles, year and aniSearch type,
+    /// ordered by descending score.
+    pub fn candidates<'t>(
+        &self,
+        titles: impl IntoIterator<Item = &'t str>,
+        year: Option<u32>,
+        r#type: Option<&str>,
+        limit: usize,
+    ) -> Vec<Candidate<'a>> {
+        let titles: Vec<Vec<[char; 2]>> = titles
+            .into_iter()
+            .map(|title| bigrams(&normalize_title(title)))
+            .collect();
+        let r#type = r#type.and_then(parse_anisearch_type);
+        let mut candidates: Vec<Candidate<'a>> = Vec::new();
+
+        for (candidate_title, anime) in self.titles.iter() {
+            let year_score = match (year, anime.anime_season.year) {
+                (Some(year), Some(candidate_year)) => match year.abs_diff(candidate_year) {
+                    0 => 1.0,
+                    1 => 0.5,
+                    // Comparing the titles is expensive, so unrelated years are skipped early
+                    _ => continue,
+                },
+                _ => 0.5,
+            };
+            let title_score = titles
+                .iter()
+                .map(|title| similarity(title, candidate_title))
+                .fold(0.0, f64::max);
+
+            if title_score < Self::MIN_TITLE_SIMILARITY {
+                continue;
+            }
+
+            let type_score = match r#type {
+                Some(r#type) if r#type == anime.r#type => 1.0,
+                Some(_) if anime.r#type != Type::Unknown => 0.0,
+                _ => 0.5,
+            };
+            let score = 0.7 * title_score + 0.2 * year_score + 0.1 * type_score;
+
+            // Every title and synonym of an anime is indexed, but it should be a candidate only once
+            match candidates
+                .iter_mut()
+                .find(|candidate| std::ptr::eq(candidate.anime, *anime))
+            {
+                Some(candidate) => candidate.score = candidate.score.max(score),
+                None => candidates.push(Candidate { anime, score }),
+            }
+        }
+
+        candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+        candidates.truncate(limit);
+        candidates
+    }
+
+    /// Returns the best candidate, if it reaches the score for being applied without review
+    /// and no other candidate does.
+    pub fn auto_apply<'c>(&self, candidates: &'c [Candidate<'a>]) -> Option<&'c Candidate<'a>> {
+        let min_score = self.auto_apply_score?;
+
+        match candidates {
+            [best, next, ..] if best.score >= min_score && next.score < min_score => Some(best),
+            [best] if best.score >= min_score => Some(best),
+            _ => None,
+        }
+    }
+}
+
+/// Maps the types shown on aniSearch to the ones of the offline database.
+fn parse_anisearch_type(r#type: &str) -> Option<Type> {
+    match r#type {
+        "TV-Series" => Some(Type::Tv),
+        "Movie" => Some(Type::Movie),
+        "OVA" => Some(Type::Ova),
+        "Web" => Some(Type::Ona),
+        "TV-Special" | "Bonus" => Some(Type::Special),
+        _ => None,
+    }
+}
+
+/// Sorted character pairs of a normalized title.
+fn bigrams(title: &str) -> Vec<[char; 2]> {
+    let chars: Vec<char> = title.chars().collect();
+    let mut bigrams: Vec<[char; 2]> = chars.windows(2).map(|pair| [pair[0], pair[1]]).collect();
+    bigrams.sort_unstable();
+    bigrams
+}
+
+/// Dice coefficient of the character pairs of two titles.
+fn similarity(a: &[[char; 2]], b: &[[char; 2]]) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return f64::from(u8::from(a == b));
+    }
+
+    let (mut i, mut j, mut shared) = (0, 0, 0);
+
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                shared += 1;
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+
+    (2 * shared) as f64 / (a.len() + b.len()) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize_title, FuzzyMatcher, TitleIndex};
+    use crate::database::Root;
+
+    fn root() -> Root {
+        serde_json::from_str(
+            r#"{
+              "license": { "name": "", "url": "" },
+              "repository": "",
+              "lastUpdate": "",
+              "data": [
+                {
+                  "sources": ["https://myanimelist.net/anime/1"],
+                  "title": "Kyaputen Fyūchā",
+                  "type": "TV", "episodes": 52, "status": "FINISHED",
+                  "animeSeason": { "season": "FALL", "year": 1978 },
+                  "picture": "", "thumbnail": "",
+                  "synonyms": ["Captain Future"], "relatedAnime": [], "tags": []
+                },
+                {
+                  "sources": ["https://myanimelist.net/anime/2"],
+                  "title": "Captain Future",
+                  "type": "SPECIAL", "episodes": 1, "status": "FINISHED",
+                  "animeSeason": { "season": "UNDEFINED", "year": 2021 },
+                  "picture": "", "thumbnail": "",
+                  "synonyms": [], "relatedAnime": [], "tags": []
+                }
+              ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_normalize_title() {
+        assert_eq!(normalize_title("Kimi no Na wa."), "kimi no na wa");
+        assert_eq!(
+            normalize_title("Your Name. - Gestern, heute"),
+            "your name gestern heute"
+        );
+    }
+
+    #[test]
+    fn test_find() {
+        let root = root();
+        let index = TitleIndex::new(&root);
+        let find_title = |titles: &[&str], year| index.find(titles.iter().copied(), year).map(|anime| anime.episodes);
+
+        // Both anime are called Captain Future, so only the year can tell them apart
+        assert_eq!(find_title(&["Captain Future"], Some(1978)), Some(52));
+        assert_eq!(find_title(&["captain future!"], Some(2021)), Some(1));
+        assert_eq!(find_title(&["Captain Future"], None), None);
+        assert_eq!(find_title(&["Unknown", "Kyaputen Fyūchā"], None), Some(52));
+        assert_eq!(find_title(&["Kyaputen Fyūchā"], Some(1990)), None);
+    }
+
+    #[test]
+    fn test_fuzzy_candidates() {
+        let root = root();
+        let matcher = FuzzyMatcher::new(&root, Some(0.9));
+        let scores = |titles: &[&str], year, r#type| {
+            matcher
+                .candidates(titles.iter().copied(), year, r#type, 5)
+                .iter()
+                .map(|candidate| (candidate.anime.episodes, (candidate.score * 100.0).round() as u32))
+                .collect::<Vec<_>>()
+        };
+
+        // Same title, year and type
+        assert_eq!(scores(&["Captain Future"], Some(1978), Some("TV-Series")), [(52, 100)]);
+        // Similar title, but the type differs
+        assert_eq!(scores(&["Captain Future!"], Some(2021), Some("TV-Series")), [(1, 90)]);
+        assert_eq!(scores(&["Captain Future"], None, None), [(52, 85), (1, 85)]);
+        assert!(scores(&["Meitantei Conan"], None, None).is_empty());
+
+        let candidates = matcher.candidates(["Captain Future"], Some(1978), Some("TV-Series"), 5);
+        assert_eq!(
+            matcher
+                .auto_apply(&candidates)
+                .map(|candidate| candidate.anime.episodes),
+            Some(52)
+        );
+        // Two equally likely candidates need a review
+        let candidates = matcher.candidates(["Captain Future"], None, None, 5);
+        assert!(matcher.auto_apply(&candidates).is_none());
+    }
+}
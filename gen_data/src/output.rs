@@ -1,31 +1,315 @@
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs::OpenOptions;
 use std::io::{BufWriter, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Serialize)]
-struct Output<'a> {
-    dubbed: &'a [u64],
-    incomplete: &'a [u64],
+use crate::changes::Changes;
+use crate::checkpoint::Checkpoint;
+use crate::database::Type;
+use crate::error::{Error, Result};
+use crate::source::DubStatus;
+use crate::state::State;
+
+/// The dub data. `dubbed` and `incomplete` keep their meaning from before the other categories existed,
+/// so `incomplete` also includes the upcoming dubs and the dubs, whose status is unknown.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Output {
+    pub dubbed: Vec<u64>,
+    /// Subset of `dubbed`
+    pub incomplete: Vec<u64>,
+    /// Subset of `incomplete`, whose dub has been announced, but not released yet
+    #[serde(default)]
+    pub upcoming: Vec<u64>,
+    /// Listed as dubbed, but the dub has never been released. Disjoint from `dubbed`.
+    #[serde(default)]
+    pub never_released: Vec<u64>,
+    /// Subset of `incomplete`, whose dub status could not be determined
+    #[serde(default)]
+    pub unknown: Vec<u64>,
+    /// Percentage of dubbed episodes per MyAnimeList id, if known
+    #[serde(default)]
+    pub progress: BTreeMap<u64, u8>,
+}
+
+/// MyAnimeList ids of the titles a company was involved in, per role.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompanyTitles {
+    pub licensor: BTreeSet<u64>,
+    pub publisher: BTreeSet<u64>,
+    pub dub_studio: BTreeSet<u64>,
+}
+
+/// Problems with mapping the dubbed anime of the sources to the offline database.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MappingReport {
+    pub unmatched: Vec<UnmatchedAnime>,
+    pub broken_links: Vec<BrokenLink>,
+    pub partial_matches: Vec<PartialMatch>,
+    pub status_conflicts: Vec<StatusConflict>,
+}
+
+/// A dubbed anime, which is listed by a source, but has no MyAnimeList id.
+#[derive(Debug, Clone, Serialize)]
+pub struct UnmatchedAnime {
+    pub source: &'static str,
+    pub url: String,
+    pub title: String,
+    pub year: Option<u32>,
+    pub r#type: Option<String>,
+}
+
+/// A link of the offline database, which is not found or has moved to another anime.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BrokenLink {
+    pub url: String,
+    pub mal_ids: Vec<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redirects_to: Option<String>,
+}
+
+/// A title, for which only some of its linked entries are listed as dubbed.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PartialMatch {
+    pub mal_ids: Vec<u64>,
+    pub listed: Vec<String>,
+    pub unlisted: Vec<String>,
+    /// Whether the validation policy counts the title as dubbed
+    pub dubbed: bool,
+}
+
+/// A title, whose linked entries have different dub statuses.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusConflict {
+    pub mal_id: u64,
+    pub statuses: BTreeMap<String, DubStatus>,
+    /// Status according to the aggregation policy
+    pub resolved: DubStatus,
+}
+
+/// MyAnimeList ids suggested for a dubbed anime without one, which need to be reviewed.
+#[derive(Debug, Clone, Serialize)]
+pub struct MatchReview {
+    #[serde(flatten)]
+    pub anime: UnmatchedAnime,
+    pub candidates: Vec<MatchCandidate>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchCandidate {
+    pub mal_ids: Vec<u64>,
+    pub title: String,
+    pub year: Option<u32>,
+    pub r#type: Type,
+    pub score: f64,
+}
+
+/// Reads the dub data of a previous run. Returns `None`, if there is none.
+pub fn read_output(path: &Path) -> Result<Option<Output>> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => {
+            return Err(Error::Io {
+                path: path.display().to_string(),
+                message: err.to_string(),
+            })
+        }
+    };
+
+    serde_json::from_str(&text).map(Some).map_err(|err| Error::InvalidFile {
+        path: path.display().to_string(),
+        message: err.to_string(),
+    })
+}
+
+pub fn write_output(path: &Path, output: &Output) -> Result<()> {
+    write_json(path, output)
+}
+
+/// Writes the dub data of several languages, keyed by their aniSearch language code.
+pub fn write_combined(path: &Path, outputs: &BTreeMap<&str, Output>) -> Result<()> {
+    write_json(path, outputs)
+}
+
+pub fn write_companies(path: &Path, companies: &BTreeMap<String, CompanyTitles>) -> Result<()> {
+    write_json(path, companies)
+}
+
+/// Writes the names of the sources, which list each dubbed MyAnimeList id.
+pub fn write_provenance(path: &Path, provenance: &BTreeMap<u64, BTreeSet<&str>>) -> Result<()> {
+    write_json(path, provenance)
+}
+
+pub fn write_mapping_report(path: &Path, report: &MappingReport) -> Result<()> {
+    write_json(path, report)
+}
+
+pub fn write_match_reviews(path: &Path, reviews: &[MatchReview]) -> Result<()> {
+    write_json(path, reviews)
+}
+
+/// Writes the checkpoint without keeping a backup, as it is written often and removed after the run.
+pub fn write_checkpoint(path: &Path, checkpoint: &Checkpoint) -> Result<()> {
+    write_atomically(path, &to_json(path, checkpoint)?)
 }
 
-pub fn write_output(path: &Path, dubbed_mal_ids: &[u64], incomplete_mal_ids: &[u64]) {
-    std::fs::create_dir_all(path.parent().unwrap()).ok();
+pub fn write_changes(path: &Path, changes: &Changes) -> Result<()> {
+    write_json(path, changes)
+}
+
+/// Writes the changes as Markdown, e.g. to be used as commit message.
+pub fn write_changes_markdown(path: &Path, changes: &Changes) -> Result<()> {
+    write_file(path, changes.to_markdown().as_bytes())
+}
+
+/// Writes the dub infos, which the next run starts from.
+pub fn write_state(path: &Path, state: &State) -> Result<()> {
+    write_json(path, state)
+}
+
+fn write_json<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<()> {
+    write_file(path, &to_json(path, value)?)
+}
+
+fn to_json<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<Vec<u8>> {
+    serde_json::to_vec_pretty(value).map_err(|err| Error::Io {
+        path: path.display().to_string(),
+        message: err.to_string(),
+    })
+}
 
-    let file = OpenOptions::new()
-        .create(true)
-        .write(true)
-        .truncate(true)
-        .open(path)
-        .expect("failed to open output file");
-    let mut writer = BufWriter::new(file);
+/// Replaces the file atomically and keeps its previous version next to it, e.g. as `dubInfo.json.bak`.
+fn write_file(path: &Path, contents: &[u8]) -> Result<()> {
+    let io_error = |message: String| Error::Io {
+        path: path.display().to_string(),
+        message,
+    };
+
+    if path.exists() {
+        let backup_path = with_suffix(path, ".bak");
+        std::fs::remove_file(&backup_path).ok();
+
+        // Linking is cheap, but not supported by every file system
+        if std::fs::hard_link(path, &backup_path).is_err() {
+            std::fs::copy(path, &backup_path).map_err(|err| io_error(format!("could not keep backup: {err}")))?;
+        }
+    }
+
+    write_atomically(path, contents)
+}
 
-    let output = Output {
-        dubbed: dubbed_mal_ids,
-        incomplete: incomplete_mal_ids,
+/// Number of temporary files created by this process, which makes their names unique across threads
+static TEMP_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// Writes to a temporary file in the same directory, which then replaces the file,
+/// so that readers see either the old or the new file, even if the run is interrupted.
+pub fn write_atomically(path: &Path, contents: &[u8]) -> Result<()> {
+    let io_error = |message: String| Error::Io {
+        path: path.display().to_string(),
+        message,
     };
-    serde_json::to_writer_pretty(&mut writer, &output).expect("failed to write to output");
 
-    writer.flush().expect("failed to flush output");
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).ok();
+    }
+
+    let temp_file = TEMP_FILES.fetch_add(1, Ordering::Relaxed);
+    let temp_path = with_suffix(path, &format!(".{}.{}.tmp", std::process::id(), temp_file));
+    let write_temp = || -> std::io::Result<()> {
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&temp_path)?;
+        let mut writer = BufWriter::new(file);
+
+        writer.write_all(contents)?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+
+        std::fs::rename(&temp_path, path)
+    };
+
+    if let Err(err) = write_temp() {
+        std::fs::remove_file(&temp_path).ok();
+        return Err(io_error(err.to_string()));
+    }
+
+    // Persist the rename as well
+    #[cfg(unix)]
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        std::fs::File::open(parent)
+            .and_then(|dir| dir.sync_all())
+            .map_err(|err| io_error(err.to_string()))?;
+    }
+
+    Ok(())
+}
+
+/// Appends `suffix` to the file name, e.g. `dubInfo.json` to `dubInfo.json.bak`.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(suffix);
+    path.with_file_name(file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_atomically, write_file};
+
+    #[test]
+    fn test_write_file() {
+        let dir = std::env::temp_dir().join(format!("mal_gerdubs_write_file_{}", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        let path = dir.join("dubInfo.json");
+
+        write_file(&path, b"old").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"old");
+        assert!(!dir.join("dubInfo.json.bak").exists());
+
+        write_file(&path, b"new").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"new");
+        assert_eq!(std::fs::read(dir.join("dubInfo.json.bak")).unwrap(), b"old");
+
+        // Only the file and its backup are left
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_concurrent_writes() {
+        let dir = std::env::temp_dir().join(format!("mal_gerdubs_concurrent_writes_{}", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        let path = dir.join("entry.json");
+        let contents: Vec<Vec<u8>> = (0..8).map(|thread| vec![b'a' + thread; 64 * 1024]).collect();
+
+        std::thread::scope(|scope| {
+            for content in contents.iter() {
+                scope.spawn(|| {
+                    for _ in 0..10 {
+                        write_atomically(&path, content).unwrap();
+                    }
+                });
+            }
+        });
+
+        // Every write replaced the file as a whole
+        assert!(contents.contains(&std::fs::read(&path).unwrap()));
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
 }
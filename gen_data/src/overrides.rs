@@ -0,0 +1,292 @@
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+use crate::source::SourceResult;
+
+/// Manual correction of the dub status of a title, e.g. when aniSearch is wrong.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Override {
+    pub target: OverrideTarget,
+    pub status: OverrideStatus,
+    /// Why the override is needed, which is mandatory
+    pub reason: String,
+    /// Day from which on the override is no longer applied
+    pub expires: Option<NaiveDate>,
+    /// aniSearch code of the language, to which the override is limited
+    pub language: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverrideTarget {
+    Mal(u64),
+    /// Stands for all MyAnimeList ids, which the aniSearch entry is linked to in the offline database
+    Anisearch(u64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverrideStatus {
+    Dubbed,
+    Incomplete,
+    NotDubbed,
+    NeverReleased,
+}
+
+/// An override with the MyAnimeList ids it applies to.
+pub type ResolvedOverride<'a> = (&'a Override, Box<[u64]>);
+
+/// Overrides, which have been checked against the generated data.
+#[derive(Debug, Default)]
+pub struct OverrideReport<'a> {
+    pub applied: Vec<&'a Override>,
+    /// Overrides, which the sources already agree with
+    pub redundant: Vec<&'a Override>,
+    pub expired: Vec<&'a Override>,
+}
+
+#[derive(Deserialize)]
+struct OverridesFile {
+    #[serde(default, rename = "override")]
+    overrides: Vec<RawOverride>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawOverride {
+    mal: Option<u64>,
+    anisearch: Option<u64>,
+    status: OverrideStatus,
+    reason: String,
+    expires: Option<toml::value::Datetime>,
+    language: Option<String>,
+}
+
+impl Display for OverrideTarget {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OverrideTarget::Mal(id) => write!(f, "https://myanimelist.net/anime/{id}"),
+            OverrideTarget::Anisearch(id) => write!(f, "https://anisearch.com/anime/{id}"),
+        }
+    }
+}
+
+/// Reads overrides from a TOML file with one `[[override]]` table per title:
+///
+/// ```toml
+/// [[override]]
+/// mal = 1535
+/// status = "incomplete"
+/// reason = "Only the first season has been dubbed"
+/// expires = 2024-06-01
+/// language = "de"
+/// ```
+///
+/// Overrides without a `language` apply to all languages.
+pub fn read_overrides(path: &Path) -> Result<Vec<Override>> {
+    let text = std::fs::read_to_string(path).map_err(|err| Error::Io {
+        path: path.display().to_string(),
+        message: err.to_string(),
+    })?;
+
+    parse(&text).map_err(|message| Error::InvalidFile {
+        path: path.display().to_string(),
+        message,
+    })
+}
+
+fn parse(text: &str) -> std::result::Result<Vec<Override>, String> {
+    let file: OverridesFile = toml::from_str(text).map_err(|err| err.message().to_string())?;
+
+    file.overrides
+        .into_iter()
+        .enumerate()
+        .map(|(index, raw)| {
+            let number = index + 1;
+            let target = match (raw.mal, raw.anisearch) {
+                (Some(mal_id), None) => OverrideTarget::Mal(mal_id),
+                (None, Some(anisearch_id)) => OverrideTarget::Anisearch(anisearch_id),
+                _ => return Err(format!("override {number} needs either a `mal` or an `anisearch` id")),
+            };
+
+            if raw.reason.trim().is_empty() {
+                return Err(format!("override {number} for {target} has no reason"));
+            }
+
+            let expires = match raw.expires {
+                Some(datetime) => {
+                    let date = datetime
+                        .date
+                        .filter(|_| datetime.time.is_none())
+                        .and_then(|date| NaiveDate::from_ymd_opt(date.year.into(), date.month.into(), date.day.into()))
+                        .ok_or_else(|| format!("override {number} for {target} has an invalid expiry date"))?;
+
+                    Some(date)
+                }
+                None => None,
+            };
+
+            Ok(Override {
+                target,
+                status: raw.status,
+                reason: raw.reason,
+                expires,
+                language: raw.language,
+            })
+        })
+        .collect()
+}
+
+/// Applies the overrides, which have not expired by `today`, to the MyAnimeList ids they resolve to.
+pub fn apply<'a>(
+    overrides: &[ResolvedOverride<'a>],
+    result: &mut SourceResult,
+    today: NaiveDate,
+) -> OverrideReport<'a> {
+    let mut report = OverrideReport::default();
+
+    for &(r#override, ref mal_ids) in overrides {
+        if r#override.expires.is_some_and(|expires| expires <= today) {
+            report.expired.push(r#override);
+            continue;
+        }
+
+        let redundant = mal_ids
+            .iter()
+            .all(|mal_id| status_of(result, *mal_id) == r#override.status);
+
+        for &mal_id in mal_ids.iter() {
+            result.dubbed.remove(&mal_id);
+            result.incomplete.remove(&mal_id);
+            result.upcoming.remove(&mal_id);
+            result.unknown.remove(&mal_id);
+            result.never_released.remove(&mal_id);
+
+            match r#override.status {
+                OverrideStatus::Dubbed => {
+                    result.dubbed.insert(mal_id);
+                }
+                OverrideStatus::Incomplete => {
+                    result.dubbed.insert(mal_id);
+                    result.incomplete.insert(mal_id);
+                }
+                OverrideStatus::NotDubbed => {}
+                OverrideStatus::NeverReleased => {
+                    result.never_released.insert(mal_id);
+                }
+            }
+        }
+
+        if redundant {
+            report.redundant.push(r#override);
+        } else {
+            report.applied.push(r#override);
+        }
+    }
+
+    report
+}
+
+fn status_of(result: &SourceResult, mal_id: u64) -> OverrideStatus {
+    if result.incomplete.contains(&mal_id) {
+        OverrideStatus::Incomplete
+    } else if result.dubbed.contains(&mal_id) {
+        OverrideStatus::Dubbed
+    } else if result.never_released.contains(&mal_id) {
+        OverrideStatus::NeverReleased
+    } else {
+        OverrideStatus::NotDubbed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use chrono::NaiveDate;
+
+    use super::{apply, parse, Override, OverrideStatus, OverrideTarget};
+    use crate::source::SourceResult;
+
+    #[test]
+    fn test_parse() {
+        let text = r#"
+            [[override]]
+            mal = 1535
+            status = "incomplete"
+            reason = "Only the first season has been dubbed"
+            expires = 2024-06-01
+            language = "de"
+
+            [[override]]
+            anisearch = 14
+            status = "never-released"
+            reason = "The dub was cancelled"
+        "#;
+
+        assert_eq!(
+            parse(text),
+            Ok(vec![
+                Override {
+                    target: OverrideTarget::Mal(1535),
+                    status: OverrideStatus::Incomplete,
+                    reason: "Only the first season has been dubbed".to_string(),
+                    expires: NaiveDate::from_ymd_opt(2024, 6, 1),
+                    language: Some("de".to_string()),
+                },
+                Override {
+                    target: OverrideTarget::Anisearch(14),
+                    status: OverrideStatus::NeverReleased,
+                    reason: "The dub was cancelled".to_string(),
+                    expires: None,
+                    language: None,
+                },
+            ])
+        );
+
+        assert_eq!(
+            parse("[[override]]\nmal = 1\nstatus = \"dubbed\"\nreason = \"\"\n"),
+            Err("override 1 for https://myanimelist.net/anime/1 has no reason".to_string())
+        );
+        assert_eq!(
+            parse("[[override]]\nmal = 1\nanisearch = 1\nstatus = \"dubbed\"\nreason = \"Both\"\n"),
+            Err("override 1 needs either a `mal` or an `anisearch` id".to_string())
+        );
+    }
+
+    #[test]
+    fn test_apply() {
+        let override_for = |status, expires| Override {
+            target: OverrideTarget::Mal(0),
+            status,
+            reason: "Test".to_string(),
+            expires,
+            language: None,
+        };
+        let dubbed = override_for(OverrideStatus::Dubbed, None);
+        let not_dubbed = override_for(OverrideStatus::NotDubbed, None);
+        let expired = override_for(OverrideStatus::NeverReleased, NaiveDate::from_ymd_opt(2023, 1, 1));
+        let today = NaiveDate::from_ymd_opt(2023, 10, 1).unwrap();
+
+        let mut result = SourceResult {
+            dubbed: BTreeSet::from([1, 2]),
+            incomplete: BTreeSet::from([2]),
+            ..Default::default()
+        };
+        let overrides = [
+            (&dubbed, Box::from([2, 3])),
+            (&not_dubbed, Box::from([4])),
+            (&expired, Box::from([1])),
+        ];
+        let report = apply(&overrides, &mut result, today);
+
+        assert_eq!(result.dubbed, BTreeSet::from([1, 2, 3]));
+        assert!(result.incomplete.is_empty());
+        assert_eq!(report.applied, [&dubbed]);
+        assert_eq!(report.redundant, [&not_dubbed]);
+        assert_eq!(report.expired, [&expired]);
+    }
+}
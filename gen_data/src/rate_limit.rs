@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::checkpoint;
+use crate::error::Result;
+
+/// Token bucket rate limiter, which keeps a separate bucket for every host.
+pub struct RateLimiter {
+    requests_per_second: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+struct Bucket {
+    requests_per_second: f64,
+    burst: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: f64, burst: u32) -> Self {
+        assert!(requests_per_second > 0.0);
+
+        Self {
+            requests_per_second,
+            burst: burst.max(1) as f64,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Makes sure that at least `interval` passes between two requests to the given host,
+    /// e.g. because of a crawl delay.
+    pub fn limit_host(&self, host: &str, interval: Duration) {
+        if interval.is_zero() {
+            return;
+        }
+
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = self.bucket(&mut buckets, host);
+        bucket.requests_per_second = bucket.requests_per_second.min(1.0 / interval.as_secs_f64());
+        bucket.burst = 1.0;
+        bucket.tokens = bucket.tokens.min(bucket.burst);
+    }
+
+    /// Blocks until a request to the given host may be sent or the run has been interrupted.
+    pub fn acquire(&self, host: &str) -> Result<()> {
+        let wait = {
+            let mut buckets = self.buckets.lock().unwrap();
+            let now = Instant::now();
+            let bucket = self.bucket(&mut buckets, host);
+
+            let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * bucket.requests_per_second).min(bucket.burst);
+            bucket.last_refill = now;
+
+            // The token is reserved right away, so waiting callers are served in order
+            bucket.tokens -= 1.0;
+
+            if bucket.tokens >= 0.0 {
+                return Ok(());
+            }
+
+            Duration::from_secs_f64(-bucket.tokens / bucket.requests_per_second)
+        };
+
+        checkpoint::sleep(wait)
+    }
+
+    fn bucket<'a>(&self, buckets: &'a mut HashMap<String, Bucket>, host: &str) -> &'a mut Bucket {
+        let host = host.strip_prefix("www.").unwrap_or(host);
+
+        buckets.entry(host.to_string()).or_insert_with(|| Bucket {
+            requests_per_second: self.requests_per_second,
+            burst: self.burst,
+            tokens: self.burst,
+            last_refill: Instant::now(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::RateLimiter;
+
+    #[test]
+    fn test_burst_then_rate_limited() {
+        let rate_limiter = RateLimiter::new(20.0, 2);
+        let start = Instant::now();
+
+        rate_limiter.acquire("anisearch.com").unwrap();
+        rate_limiter.acquire("www.anisearch.com").unwrap();
+        assert!(start.elapsed() < Duration::from_millis(40));
+
+        rate_limiter.acquire("anisearch.com").unwrap();
+        rate_limiter.acquire("anisearch.com").unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(95));
+
+        let other_host_start = Instant::now();
+        rate_limiter.acquire("example.com").unwrap();
+        assert!(other_host_start.elapsed() < Duration::from_millis(40));
+    }
+
+    #[test]
+    fn test_limit_host() {
+        let rate_limiter = RateLimiter::new(1000.0, 10);
+        rate_limiter.limit_host("anisearch.com", Duration::from_millis(50));
+        let start = Instant::now();
+
+        rate_limiter.acquire("www.anisearch.com").unwrap();
+        rate_limiter.acquire("anisearch.com").unwrap();
+        rate_limiter.acquire("anisearch.com").unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(95));
+    }
+}
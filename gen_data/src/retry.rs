@@ -0,0 +1,127 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::time::Duration;
+
+/// How often and how long failed requests are retried.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts per URL, including the first one
+    pub max_attempts: u32,
+    /// Maximum time spent on a single URL, including all waits
+    pub max_total_time: Duration,
+    /// Base delay for the exponential backoff after transport errors and server errors
+    pub base_delay: Duration,
+    /// Base delay for the exponential backoff after 429 Too Many Requests without Retry-After
+    pub rate_limited_base_delay: Duration,
+    /// Upper bound for a single backoff delay
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            max_total_time: Duration::from_secs(15 * 60),
+            base_delay: Duration::from_secs(10),
+            rate_limited_base_delay: Duration::from_secs(60),
+            max_delay: Duration::from_secs(300),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before the given retry (starting at 1), doubled for every retry and randomized
+    /// between 50 % and 100 %, so concurrent requests do not retry at the same moment.
+    pub fn backoff(&self, base_delay: Duration, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        let delay = base_delay.saturating_mul(factor).min(self.max_delay);
+
+        delay.mul_f64(0.5 + fastrand::f64() * 0.5)
+    }
+}
+
+/// Parses a Retry-After header value, which is either in seconds or an HTTP date.
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let seconds = (date.timestamp() - chrono::Utc::now().timestamp()).max(0);
+
+    Some(Duration::from_secs(seconds as u64))
+}
+
+/// Stops all requests of a run, after too many requests in a row failed because the site seems to be down.
+pub struct CircuitBreaker {
+    threshold: u32,
+    consecutive_failures: AtomicU32,
+    open: AtomicBool,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            consecutive_failures: AtomicU32::new(0),
+            open: AtomicBool::new(false),
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open.load(Ordering::Relaxed)
+    }
+
+    pub fn record_success(&self) {
+        self.consecutive_failures.store(0, Ordering::Relaxed);
+    }
+
+    /// Returns true, if this failure opened the circuit.
+    pub fn record_failure(&self) -> bool {
+        let failures = self.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+
+        failures >= self.threshold && !self.open.swap(true, Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{parse_retry_after, CircuitBreaker, RetryPolicy};
+
+    #[test]
+    fn test_backoff() {
+        let retry_policy = RetryPolicy::default();
+
+        for retry in 1..=10 {
+            let delay = retry_policy.backoff(Duration::from_secs(10), retry);
+            let expected = Duration::from_secs(10 * 2u64.pow(retry - 1)).min(retry_policy.max_delay);
+
+            assert!(delay >= expected / 2 && delay <= expected, "retry {retry}: {delay:?}");
+        }
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[test]
+    fn test_circuit_breaker() {
+        let circuit_breaker = CircuitBreaker::new(3);
+
+        assert!(!circuit_breaker.record_failure());
+        assert!(!circuit_breaker.record_failure());
+        circuit_breaker.record_success();
+        assert!(!circuit_breaker.record_failure());
+        assert!(!circuit_breaker.record_failure());
+        assert!(!circuit_breaker.is_open());
+        assert!(circuit_breaker.record_failure());
+        assert!(circuit_breaker.is_open());
+        assert!(!circuit_breaker.record_failure());
+    }
+}
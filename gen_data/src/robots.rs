@@ -0,0 +1,187 @@
+use std::time::Duration;
+
+/// Parsed robots.txt, see RFC 9309.
+#[derive(Debug, Default)]
+pub struct RobotsTxt {
+    groups: Vec<Group>,
+}
+
+#[derive(Debug, Default)]
+struct Group {
+    user_agents: Vec<String>,
+    rules: Vec<Rule>,
+    crawl_delay: Option<Duration>,
+}
+
+#[derive(Debug)]
+struct Rule {
+    allow: bool,
+    pattern: String,
+}
+
+impl RobotsTxt {
+    /// A robots.txt, which allows everything, e.g. if the site has none.
+    pub fn allow_all() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(content: &str) -> Self {
+        let mut groups: Vec<Group> = Vec::new();
+        let mut current = Group::default();
+        let mut in_user_agent_lines = false;
+
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let key = key.trim().to_ascii_lowercase();
+            let value = value.trim();
+
+            match key.as_str() {
+                "user-agent" => {
+                    // Consecutive user-agent lines belong to the same group
+                    if !in_user_agent_lines && !current.user_agents.is_empty() {
+                        groups.push(std::mem::take(&mut current));
+                    }
+
+                    current.user_agents.push(value.to_ascii_lowercase());
+                    in_user_agent_lines = true;
+                }
+                "allow" | "disallow" => {
+                    in_user_agent_lines = false;
+
+                    // An empty disallow rule allows everything
+                    if !value.is_empty() {
+                        current.rules.push(Rule {
+                            allow: key == "allow",
+                            pattern: value.to_string(),
+                        });
+                    }
+                }
+                "crawl-delay" => {
+                    in_user_agent_lines = false;
+                    current.crawl_delay = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+                        .map(Duration::from_secs_f64);
+                }
+                _ => {}
+            }
+        }
+
+        if !current.user_agents.is_empty() {
+            groups.push(current);
+        }
+
+        Self { groups }
+    }
+
+    /// Returns true, if the path (including the query) may be fetched by the crawler with the given product token.
+    pub fn is_allowed(&self, product_token: &str, path: &str) -> bool {
+        let Some(group) = self.group(product_token) else {
+            return true;
+        };
+
+        // The longest matching rule wins, allow wins on ties
+        group
+            .rules
+            .iter()
+            .filter(|rule| matches_pattern(&rule.pattern, path))
+            .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+            .map(|rule| rule.allow)
+            .unwrap_or(true)
+    }
+
+    pub fn crawl_delay(&self, product_token: &str) -> Option<Duration> {
+        self.group(product_token).and_then(|group| group.crawl_delay)
+    }
+
+    fn group(&self, product_token: &str) -> Option<&Group> {
+        let product_token = product_token.to_ascii_lowercase();
+
+        self.groups
+            .iter()
+            .find(|group| group.user_agents.contains(&product_token))
+            .or_else(|| {
+                self.groups
+                    .iter()
+                    .find(|group| group.user_agents.iter().any(|user_agent| user_agent == "*"))
+            })
+    }
+}
+
+/// Matches a robots.txt path pattern, which may contain `*` wildcards and end with `$`.
+fn matches_pattern(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+
+    let Some(mut rest) = path.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+
+    for (index, part) in parts.iter().enumerate() {
+        let is_last = index == parts.len() - 1;
+
+        if is_last && anchored {
+            return rest.ends_with(part);
+        }
+
+        match rest.find(part) {
+            Some(position) => rest = &rest[position + part.len()..],
+            None => return false,
+        }
+    }
+
+    !anchored || rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::RobotsTxt;
+
+    const ROBOTS_TXT: &str = "
+User-agent: *
+Disallow: /search
+Disallow: /*?sort=
+Allow: /anime/index
+Crawl-delay: 2
+
+User-agent: mal_gerdubs
+User-agent: otherbot
+Disallow: /anime/index/page-*$
+Allow: /anime/index/page-1$
+Crawl-delay: 0.5
+";
+
+    #[test]
+    fn test_parse_and_match() {
+        let robots_txt = RobotsTxt::parse(ROBOTS_TXT);
+
+        assert!(!robots_txt.is_allowed("somebot", "/search?q=conan"));
+        assert!(!robots_txt.is_allowed("somebot", "/anime?sort=title"));
+        assert!(robots_txt.is_allowed("somebot", "/anime/index?sort=title"));
+        assert!(robots_txt.is_allowed("somebot", "/anime/14"));
+        assert_eq!(robots_txt.crawl_delay("somebot"), Some(Duration::from_secs(2)));
+
+        assert!(robots_txt.is_allowed("MAL_GerDubs", "/search"));
+        assert!(robots_txt.is_allowed("mal_gerdubs", "/anime/index/page-1"));
+        assert!(!robots_txt.is_allowed("mal_gerdubs", "/anime/index/page-2"));
+        assert_eq!(robots_txt.crawl_delay("mal_gerdubs"), Some(Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn test_allow_all() {
+        assert!(RobotsTxt::allow_all().is_allowed("mal_gerdubs", "/anime/14"));
+        assert!(RobotsTxt::parse("User-agent: *\nDisallow:\n").is_allowed("mal_gerdubs", "/anime/14"));
+    }
+}
@@ -0,0 +1,248 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::output::{BrokenLink, CompanyTitles, MatchReview, PartialMatch, StatusConflict, UnmatchedAnime};
+use crate::state::EntryState;
+
+/// A site, which knows the dubbed anime of a language.
+///
+/// If the offline database links the site, titles are identified by their URL in its `sources`,
+/// e.g. `https://anisearch.com/anime/14`, so they can be mapped to MyAnimeList ids.
+/// Otherwise, they are matched to the offline database by title and year.
+pub trait DubSource: Sync {
+    /// Name of the source, as used in log messages
+    fn name(&self) -> &'static str;
+
+    /// Prefix of the offline database `sources`, which belong to this source,
+    /// or `None`, if the offline database does not link this source
+    fn database_prefix(&self) -> Option<&'static str>;
+
+    /// Maximum number of requests, which should be sent concurrently to this source
+    fn max_in_flight(&self) -> usize;
+
+    /// Lists one page of the dubbed anime, starting with page 1.
+    fn get_dubbed_anime_list(&self, page: u64) -> Result<DubbedAnime>;
+
+    /// Gets the dub status and details for a title listed by [`DubSource::get_dubbed_anime_list`].
+    fn get_dub_info(&self, url: &str) -> Result<DubInfo>;
+}
+
+pub struct DubbedAnime {
+    pub total_pages: u64,
+    pub entries: Box<[ListedAnime]>,
+}
+
+/// An anime as listed by a source.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ListedAnime {
+    /// URL of the anime, as used in the offline database if it links the source
+    pub url: String,
+    pub title: String,
+    /// Further titles, e.g. the original title next to a translated one
+    pub synonyms: Vec<String>,
+    /// Year in which the anime was first released
+    pub year: Option<u32>,
+    /// Type of the anime, e.g. `TV-Series` or `Movie`
+    pub r#type: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DubInfo {
+    pub status: DubStatus,
+    /// Number of dubbed episodes, e.g. `12/24`
+    pub episodes: Option<DubEpisodes>,
+    /// First and last year in which the dub was released
+    pub years: Option<(u32, Option<u32>)>,
+    pub licensors: Vec<String>,
+    pub publishers: Vec<String>,
+    pub dub_studios: Vec<String>,
+    /// URL under which the source lists the anime now, if it differs from the requested one
+    pub moved_to: Option<String>,
+    /// Hash of the part of the page, which the dub info has been parsed from
+    pub content_hash: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DubEpisodes {
+    pub dubbed: u32,
+    /// Total number of episodes, if the source lists it next to the dubbed episodes
+    pub total: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DubStatus {
+    Complete,
+    Incomplete,
+    Upcoming,
+    NeverReleased,
+}
+
+impl DubStatus {
+    /// Orders the statuses from worst to best for someone, who wants to watch the dub.
+    pub const fn rank(&self) -> u8 {
+        match self {
+            DubStatus::NeverReleased => 0,
+            DubStatus::Upcoming => 1,
+            DubStatus::Incomplete => 2,
+            DubStatus::Complete => 3,
+        }
+    }
+}
+
+/// Dub data of a single source, keyed by MyAnimeList id.
+#[derive(Debug, Default)]
+pub struct SourceResult {
+    pub dubbed: BTreeSet<u64>,
+    /// Subset of `dubbed`
+    pub incomplete: BTreeSet<u64>,
+    /// Subset of `incomplete`, whose dub has been announced, but not released yet
+    pub upcoming: BTreeSet<u64>,
+    /// Subset of `incomplete`, whose dub status could not be determined
+    pub unknown: BTreeSet<u64>,
+    /// Listed as dubbed, but the dub has never been released. Disjoint from `dubbed`.
+    pub never_released: BTreeSet<u64>,
+    pub progress: BTreeMap<u64, u8>,
+    pub companies: BTreeMap<String, CompanyTitles>,
+    /// Names of the sources, which list a dubbed title
+    pub provenance: BTreeMap<u64, BTreeSet<&'static str>>,
+    /// Listed anime, which could not be mapped to the offline database
+    pub unmatched: Vec<UnmatchedAnime>,
+    /// Links of the offline database, which are not found or lead to another anime
+    pub broken_links: Vec<BrokenLink>,
+    /// Titles, for which only some of their linked entries are listed
+    pub partial_matches: Vec<PartialMatch>,
+    /// Titles, whose linked entries have different dub statuses
+    pub status_conflicts: Vec<StatusConflict>,
+    /// Suggested MyAnimeList ids for unmatched anime, which need to be reviewed
+    pub match_reviews: Vec<MatchReview>,
+    /// Dub infos of the checked entries, keyed by their URL
+    pub state: BTreeMap<String, EntryState>,
+}
+
+impl SourceResult {
+    fn knows(&self, mal_id: u64) -> bool {
+        self.dubbed.contains(&mal_id) || self.never_released.contains(&mal_id)
+    }
+}
+
+/// Merges the results of several sources, ordered by descending precedence.
+///
+/// The first source, which knows a title, decides whether it is dubbed and whether the dub is complete.
+/// The progress is taken from the first source, which reports it, and the companies, provenance
+/// and mapping problems of all sources are combined.
+pub fn merge(results: Vec<SourceResult>) -> SourceResult {
+    let mut merged = SourceResult::default();
+
+    for (index, result) in results.iter().enumerate() {
+        let higher_precedence = &results[..index];
+
+        for &mal_id in result.dubbed.iter().chain(result.never_released.iter()) {
+            if higher_precedence.iter().any(|other| other.knows(mal_id)) {
+                continue;
+            }
+
+            if result.never_released.contains(&mal_id) {
+                merged.never_released.insert(mal_id);
+            } else {
+                merged.dubbed.insert(mal_id);
+
+                if result.incomplete.contains(&mal_id) {
+                    merged.incomplete.insert(mal_id);
+                }
+
+                if result.upcoming.contains(&mal_id) {
+                    merged.upcoming.insert(mal_id);
+                }
+
+                if result.unknown.contains(&mal_id) {
+                    merged.unknown.insert(mal_id);
+                }
+            }
+        }
+
+        for (&mal_id, &percentage) in result.progress.iter() {
+            merged.progress.entry(mal_id).or_insert(percentage);
+        }
+
+        for (company, titles) in result.companies.iter() {
+            let merged_titles = merged.companies.entry(company.clone()).or_default();
+            merged_titles.licensor.extend(titles.licensor.iter());
+            merged_titles.publisher.extend(titles.publisher.iter());
+            merged_titles.dub_studio.extend(titles.dub_studio.iter());
+        }
+
+        for (&mal_id, sources) in result.provenance.iter() {
+            merged.provenance.entry(mal_id).or_default().extend(sources.iter());
+        }
+
+        merged.unmatched.extend(result.unmatched.iter().cloned());
+        merged.broken_links.extend(result.broken_links.iter().cloned());
+        merged.partial_matches.extend(result.partial_matches.iter().cloned());
+        merged.status_conflicts.extend(result.status_conflicts.iter().cloned());
+        merged.match_reviews.extend(result.match_reviews.iter().cloned());
+        merged.state.extend(
+            result
+                .state
+                .iter()
+                .map(|(url, entry_state)| (url.clone(), entry_state.clone())),
+        );
+    }
+
+    let dubbed = &merged.dubbed;
+    merged.provenance.retain(|mal_id, _| dubbed.contains(mal_id));
+
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use super::{merge, SourceResult};
+
+    #[test]
+    fn test_merge_precedence() {
+        let preferred = SourceResult {
+            dubbed: BTreeSet::from([1, 2]),
+            incomplete: BTreeSet::from([2]),
+            unknown: BTreeSet::from([2]),
+            never_released: BTreeSet::from([3]),
+            progress: BTreeMap::from([(2, 50)]),
+            provenance: BTreeMap::from([(1, BTreeSet::from(["a"])), (2, BTreeSet::from(["a"]))]),
+            ..Default::default()
+        };
+        let fallback = SourceResult {
+            dubbed: BTreeSet::from([2, 3, 4]),
+            incomplete: BTreeSet::from([2, 4]),
+            upcoming: BTreeSet::from([2, 4]),
+            progress: BTreeMap::from([(2, 100), (4, 25)]),
+            provenance: BTreeMap::from([
+                (2, BTreeSet::from(["b"])),
+                (3, BTreeSet::from(["b"])),
+                (4, BTreeSet::from(["b"])),
+            ]),
+            ..Default::default()
+        };
+
+        let merged = merge(vec![preferred, fallback]);
+
+        assert_eq!(merged.dubbed, BTreeSet::from([1, 2, 4]));
+        assert_eq!(merged.incomplete, BTreeSet::from([2, 4]));
+        assert_eq!(merged.upcoming, BTreeSet::from([4]));
+        assert_eq!(merged.unknown, BTreeSet::from([2]));
+        assert_eq!(merged.never_released, BTreeSet::from([3]));
+        assert_eq!(merged.progress, BTreeMap::from([(2, 50), (4, 25)]));
+        assert_eq!(
+            merged.provenance,
+            BTreeMap::from([
+                (1, BTreeSet::from(["a"])),
+                (2, BTreeSet::from(["a", "b"])),
+                (4, BTreeSet::from(["b"])),
+            ])
+        );
+    }
+}
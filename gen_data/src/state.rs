@@ -0,0 +1,106 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::source::{DubInfo, DubStatus};
+
+/// What is known about the entries of the sources from previous runs, keyed by their URL.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct State {
+    pub entries: BTreeMap<String, EntryState>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryState {
+    /// When the entry has last been checked, in RFC 3339
+    pub last_checked: String,
+    pub dub_info: DubInfo,
+}
+
+impl EntryState {
+    pub fn new(dub_info: DubInfo, now: DateTime<Utc>) -> Self {
+        Self {
+            last_checked: now.to_rfc3339(),
+            dub_info,
+        }
+    }
+
+    /// Whether the entry has to be checked again, because its dub may still change
+    /// or it has not been checked for `recheck_after`.
+    pub fn needs_check(&self, now: DateTime<Utc>, recheck_after: Duration) -> bool {
+        let last_checked = match DateTime::parse_from_rfc3339(&self.last_checked) {
+            Ok(last_checked) => last_checked.with_timezone(&Utc),
+            Err(_) => return true,
+        };
+
+        self.dub_info.status != DubStatus::Complete
+            || self.dub_info.moved_to.is_some()
+            || now - last_checked >= recheck_after
+    }
+}
+
+/// Reads the state of a previous run. A missing file is treated as an empty state.
+pub fn read_state(path: &Path) -> Result<State> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(State::default()),
+        Err(err) => {
+            return Err(Error::Io {
+                path: path.display().to_string(),
+                message: err.to_string(),
+            })
+        }
+    };
+
+    serde_json::from_str(&text).map_err(|err| Error::InvalidFile {
+        path: path.display().to_string(),
+        message: err.to_string(),
+    })
+}
+
+/// Hashes the part of a page, which the dub info is parsed from, with 64-bit FNV-1a.
+///
+/// Unlike the hashers of the standard library, the hash is stable across Rust versions.
+pub fn content_hash(content: &str) -> String {
+    format!("{:016x}", crate::cache::fnv1a_hash(content.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+
+    use super::{content_hash, EntryState};
+    use crate::source::{DubInfo, DubStatus};
+
+    #[test]
+    fn test_needs_check() {
+        let checked = Utc.with_ymd_and_hms(2023, 10, 1, 12, 0, 0).unwrap();
+        let dub_info = |status| DubInfo {
+            status,
+            episodes: None,
+            years: None,
+            licensors: Vec::new(),
+            publishers: Vec::new(),
+            dub_studios: Vec::new(),
+            moved_to: None,
+            content_hash: None,
+        };
+        let complete = EntryState::new(dub_info(DubStatus::Complete), checked);
+        let incomplete = EntryState::new(dub_info(DubStatus::Incomplete), checked);
+        let recheck_after = Duration::days(30);
+
+        assert!(!complete.needs_check(checked + Duration::days(29), recheck_after));
+        assert!(complete.needs_check(checked + Duration::days(30), recheck_after));
+        assert!(incomplete.needs_check(checked + Duration::days(1), recheck_after));
+    }
+
+    #[test]
+    fn test_content_hash() {
+        assert_eq!(content_hash(""), "cbf29ce484222325");
+        assert_eq!(content_hash("a"), "af63dc4c8601ec8c");
+    }
+}
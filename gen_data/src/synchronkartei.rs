@@ -0,0 +1,295 @@
+use scraper::Selector;
+
+use crate::cache::PageClass;
+use crate::error::{Error, Result};
+use crate::fetch::{FetchConfig, Fetcher};
+use crate::source::{DubEpisodes, DubInfo, DubSource, DubStatus, DubbedAnime, ListedAnime};
+use crate::state;
+
+const SELECTOR_PAGE_INFO: &str = "div.pagination";
+const SELECTOR_PRODUCTION: &str = "dl.production";
+const PRODUCTION_URL_PREFIX: &str = "https://synchronkartei.de/";
+pub const DEFAULT_SYNCHRONKARTEI_URL: &str = "https://www.synchronkartei.de";
+
+/// Client for a German dubbing database in the style of synchronkartei.de.
+///
+/// The offline database does not link its productions, so they are matched by title and year.
+/// It only lists German dubs, which have been released.
+pub struct SynchronkarteiClient {
+    fetcher: Fetcher,
+    base_url: String,
+    selector_page_info: Selector,
+    selector_production_row: Selector,
+    selector_production_link: Selector,
+    selector_production_original_title: Selector,
+    selector_production_year: Selector,
+    selector_production: Selector,
+    selector_production_term: Selector,
+}
+
+impl SynchronkarteiClient {
+    pub fn new(base_url: &str, config: FetchConfig) -> Self {
+        Self {
+            fetcher: Fetcher::new(config),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            selector_page_info: Selector::parse(SELECTOR_PAGE_INFO).unwrap(),
+            selector_production_row: Selector::parse("table.productions tbody tr").unwrap(),
+            selector_production_link: Selector::parse("td.title > a[href]").unwrap(),
+            selector_production_original_title: Selector::parse("td.title > span.original").unwrap(),
+            selector_production_year: Selector::parse("td.year").unwrap(),
+            selector_production: Selector::parse(SELECTOR_PRODUCTION).unwrap(),
+            selector_production_term: Selector::parse("dt").unwrap(),
+        }
+    }
+
+    /// Lists the anime productions with a German dub, e.g. `https://synchronkartei.de/serie/1234`.
+    pub fn get_dubbed_anime_list(&self, page: u64) -> Result<DubbedAnime> {
+        let url = format!("{}/anime/seite-{page}", self.base_url);
+        let document = self.fetcher.get_page(&url, PageClass::Listing)?;
+        let page_info = document
+            .select(&self.selector_page_info)
+            .next()
+            .ok_or_else(|| Error::SelectorMissing {
+                url: url.clone(),
+                selector: SELECTOR_PAGE_INFO.to_string(),
+            })?
+            .text()
+            .collect::<String>();
+        let total_pages = page_info
+            .split_whitespace()
+            .last()
+            .and_then(|total| total.parse::<u64>().ok())
+            .ok_or_else(|| Error::UnexpectedLayout {
+                url: url.clone(),
+                reason: format!("no page count in: {}", page_info.trim()),
+            })?;
+        let text_of = |row: &scraper::ElementRef, selector: &Selector| {
+            row.select(selector)
+                .next()
+                .map(|element| element.text().collect::<String>().trim().to_string())
+                .filter(|text| !text.is_empty())
+        };
+
+        let entries = document
+            .select(&self.selector_production_row)
+            .filter_map(|row| {
+                let link = row.select(&self.selector_production_link).next()?;
+                let href = link.value().attr("href")?;
+                let production_url = self
+                    .format_production_url(href)
+                    .map_err(|err| log::error!("{}", err))
+                    .ok()?;
+
+                Some(ListedAnime {
+                    url: production_url,
+                    title: link.text().collect::<String>().trim().to_string(),
+                    synonyms: text_of(&row, &self.selector_production_original_title)
+                        .into_iter()
+                        .collect(),
+                    year: text_of(&row, &self.selector_production_year).and_then(|year| year.parse().ok()),
+                    r#type: None,
+                })
+            })
+            .collect();
+
+        Ok(DubbedAnime { total_pages, entries })
+    }
+
+    /// Gets the dubbed episodes and companies of a production.
+    ///
+    /// Productions are only listed once their dub has been released, so the dub is complete,
+    /// unless fewer episodes than the total are dubbed.
+    pub fn get_dub_info(&self, production_url: &str) -> Result<DubInfo> {
+        let page_url = match production_url.strip_prefix(PRODUCTION_URL_PREFIX) {
+            Some(path) => format!("{}/{}", self.base_url, path),
+            None => return Err(Error::InvalidUrl(production_url.to_string())),
+        };
+        let document = self.fetcher.get_page(&page_url, PageClass::Detail)?;
+        let production = document
+            .select(&self.selector_production)
+            .next()
+            .ok_or_else(|| Error::SelectorMissing {
+                url: page_url.clone(),
+                selector: SELECTOR_PRODUCTION.to_string(),
+            })?;
+
+        let mut dub_info = DubInfo {
+            status: DubStatus::Complete,
+            episodes: None,
+            years: None,
+            licensors: Vec::new(),
+            publishers: Vec::new(),
+            dub_studios: Vec::new(),
+            moved_to: None,
+            content_hash: Some(state::content_hash(&production.html())),
+        };
+
+        for term in production.select(&self.selector_production_term) {
+            let Some(definition) = term
+                .next_siblings()
+                .filter_map(scraper::ElementRef::wrap)
+                .find(|element| element.value().name() == "dd")
+            else {
+                continue;
+            };
+            let value = definition.text().collect::<String>().trim().to_string();
+
+            match term.text().collect::<String>().trim() {
+                "Synchronfirma" => dub_info.dub_studios.push(value),
+                "Verleih" => dub_info.publishers.push(value),
+                "Lizenz" => dub_info.licensors.push(value),
+                "Episoden" => dub_info.episodes = parse_episodes(&value),
+                "Erstausstrahlung" => dub_info.years = value.parse().ok().map(|year| (year, None)),
+                other => log::debug!("Ignoring unknown term `{}` on: {}", other, page_url),
+            }
+        }
+
+        if let Some(DubEpisodes {
+            dubbed,
+            total: Some(total),
+        }) = dub_info.episodes
+        {
+            if dubbed < total {
+                dub_info.status = DubStatus::Incomplete;
+            }
+        }
+
+        Ok(dub_info)
+    }
+
+    fn format_production_url(&self, href: &str) -> Result<String> {
+        // /serie/1234 or https://www.synchronkartei.de/serie/1234
+        // -> https://synchronkartei.de/serie/1234
+        let path = href
+            .strip_prefix(&self.base_url)
+            .or_else(|| href.strip_prefix(DEFAULT_SYNCHRONKARTEI_URL))
+            .or_else(|| href.strip_prefix(PRODUCTION_URL_PREFIX.trim_end_matches('/')))
+            .unwrap_or(href)
+            .trim_start_matches('/');
+
+        match path.split_once('/') {
+            Some(("serie" | "film", id)) if !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()) => {
+                Ok(format!("{PRODUCTION_URL_PREFIX}{path}"))
+            }
+            _ => Err(Error::InvalidUrl(href.to_string())),
+        }
+    }
+}
+
+impl DubSource for SynchronkarteiClient {
+    fn name(&self) -> &'static str {
+        "Synchronkartei"
+    }
+
+    fn database_prefix(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn max_in_flight(&self) -> usize {
+        self.fetcher.max_in_flight()
+    }
+
+    fn get_dubbed_anime_list(&self, page: u64) -> Result<DubbedAnime> {
+        SynchronkarteiClient::get_dubbed_anime_list(self, page)
+    }
+
+    fn get_dub_info(&self, url: &str) -> Result<DubInfo> {
+        SynchronkarteiClient::get_dub_info(self, url)
+    }
+}
+
+/// Parses the dubbed episodes from e.g. `12/26` or `52`.
+fn parse_episodes(text: &str) -> Option<DubEpisodes> {
+    match text.split_once('/') {
+        Some((dubbed, total)) => Some(DubEpisodes {
+            dubbed: dubbed.trim().parse().ok()?,
+            total: Some(total.trim().parse().ok()?),
+        }),
+        None => Some(DubEpisodes {
+            dubbed: text.trim().parse().ok()?,
+            total: None,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{SynchronkarteiClient, DEFAULT_SYNCHRONKARTEI_URL};
+    use crate::archive::{Archive, ArchiveMode};
+    use crate::fetch::FetchConfig;
+    use crate::source::{DubEpisodes, DubInfo, DubStatus, ListedAnime};
+
+    fn replay_client() -> SynchronkarteiClient {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/replay");
+
+        SynchronkarteiClient::new(
+            DEFAULT_SYNCHRONKARTEI_URL,
+            FetchConfig {
+                archive: Some(ArchiveMode::Replay(Archive::new(&fixtures))),
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_get_dubbed_anime_list() {
+        let dubbed_anime = replay_client().get_dubbed_anime_list(1).unwrap();
+
+        assert_eq!(dubbed_anime.total_pages, 1);
+        assert_eq!(
+            dubbed_anime.entries.as_ref(),
+            [
+                ListedAnime {
+                    url: "https://synchronkartei.de/serie/1977".to_string(),
+                    title: "Captain Future".to_string(),
+                    synonyms: vec!["Kyaputen Fyūchā".to_string()],
+                    year: Some(1978),
+                    r#type: None,
+                },
+                ListedAnime {
+                    url: "https://synchronkartei.de/film/28421".to_string(),
+                    title: "Your Name. - Gestern, heute und für immer".to_string(),
+                    synonyms: vec!["Kimi no Na wa.".to_string()],
+                    year: Some(2016),
+                    r#type: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_dub_info() {
+        let client = replay_client();
+
+        assert_eq!(
+            client
+                .get_dub_info("https://synchronkartei.de/serie/1977")
+                // The hash is tested in the state module
+                .map(|dub_info| DubInfo {
+                    content_hash: None,
+                    ..dub_info
+                }),
+            Ok(DubInfo {
+                status: DubStatus::Incomplete,
+                episodes: Some(DubEpisodes {
+                    dubbed: 40,
+                    total: Some(52),
+                }),
+                years: Some((1980, None)),
+                licensors: Vec::new(),
+                publishers: vec!["Universum Anime".to_string()],
+                dub_studios: vec!["Studio Hamburg Synchron".to_string()],
+                moved_to: None,
+                content_hash: None,
+            })
+        );
+        assert_eq!(
+            client
+                .get_dub_info("https://synchronkartei.de/film/28421")
+                .map(|info| info.status),
+            Ok(DubStatus::Complete)
+        );
+    }
+}
@@ -0,0 +1,746 @@
+//! End-to-end tests, which run the generator against a local stand-in for aniSearch.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread::JoinHandle;
+
+use serde_json::Value;
+
+struct MockSite {
+    base_url: String,
+    server: std::sync::Arc<tiny_http::Server>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl MockSite {
+    /// Serves the canned pages from `tests/fixtures/mock_site`.
+    ///
+    /// `/anime/index/page-2?...` is answered with `anime_index_page-2.html`
+    /// and `/anime/14` with `anime_14.html`. Everything else is a 404.
+    ///
+    /// Below `/flaky`, every page first fails once with 503 Service Unavailable,
+    /// while below `/unavailable` every request fails with it.
+    fn start() -> Self {
+        Self::start_with_robots_txt(None)
+    }
+
+    /// Like [`MockSite::start`], but serves the given robots.txt, which first fails once as well.
+    fn start_with_robots_txt(robots_txt: Option<&'static str>) -> Self {
+        let server = std::sync::Arc::new(tiny_http::Server::http("127.0.0.1:0").unwrap());
+        let base_url = format!("http://{}", server.server_addr().to_ip().unwrap());
+        let handle = {
+            let server = server.clone();
+
+            std::thread::spawn(move || {
+                let mut failed_once = std::collections::HashSet::new();
+
+                for request in server.incoming_requests() {
+                    let url = request.url().to_string();
+                    let mut path = url.split('?').next().unwrap_or_default();
+
+                    if let Some(flaky_path) = path.strip_prefix("/flaky") {
+                        if failed_once.insert(url.clone()) {
+                            request.respond(service_unavailable()).ok();
+                            continue;
+                        }
+
+                        path = flaky_path;
+                    } else if path.starts_with("/unavailable") {
+                        request.respond(service_unavailable()).ok();
+                        continue;
+                    }
+
+                    if let (Some(robots_txt), "/robots.txt") = (robots_txt, path) {
+                        if failed_once.insert(url.clone()) {
+                            request.respond(service_unavailable()).ok();
+                            continue;
+                        }
+
+                        request.respond(tiny_http::Response::from_string(robots_txt)).ok();
+                        continue;
+                    }
+
+                    let file_name = format!("{}.html", path.trim_start_matches('/').replace('/', "_"));
+
+                    let response = match std::fs::read_to_string(fixtures_dir().join(file_name)) {
+                        Ok(body) => tiny_http::Response::from_string(body).with_header(
+                            "Content-Type: text/html; charset=UTF-8"
+                                .parse::<tiny_http::Header>()
+                                .unwrap(),
+                        ),
+                        Err(_) => tiny_http::Response::from_string("Not Found").with_status_code(404),
+                    };
+
+                    request.respond(response).ok();
+                }
+            })
+        };
+
+        Self {
+            base_url,
+            server,
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for MockSite {
+    fn drop(&mut self) {
+        self.server.unblock();
+
+        if let Some(handle) = self.handle.take() {
+            handle.join().ok();
+        }
+    }
+}
+
+fn service_unavailable() -> tiny_http::Response<std::io::Empty> {
+    tiny_http::Response::empty(503).with_header("Retry-After: 0".parse::<tiny_http::Header>().unwrap())
+}
+
+fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/mock_site")
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mal_gerdubs_{}_{}", name, std::process::id()));
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn generator_command(base_url: &str, output_dir: &Path, extra_args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_mal_gerdubs"));
+    command
+        .arg("--base-url")
+        .arg(base_url)
+        .arg("--database")
+        .arg(fixtures_dir().join("anime-offline-database.json"))
+        .arg("--output")
+        .arg(output_dir.join("dubInfo.json"))
+        .arg("--checkpoint")
+        .arg(output_dir.join("checkpoint.json"))
+        .args(["--no-cache", "--requests-per-second", "1000", "--burst", "100"])
+        .args(extra_args);
+    command
+}
+
+fn run_generator(base_url: &str, output_dir: &Path, extra_args: &[&str]) -> std::process::Output {
+    generator_command(base_url, output_dir, extra_args).output().unwrap()
+}
+
+fn assert_success(result: &std::process::Output) {
+    assert!(
+        result.status.success(),
+        "generator failed: {}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+}
+
+fn read_output(path: &Path) -> Value {
+    serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+fn read_ids(output: &Value, key: &str) -> Vec<u64> {
+    output[key]
+        .as_array()
+        .unwrap_or_else(|| panic!("missing `{key}` in output"))
+        .iter()
+        .map(|id| id.as_u64().unwrap())
+        .collect()
+}
+
+#[test]
+fn test_generate_from_mock_site() {
+    let site = MockSite::start();
+    let output_dir = temp_dir("mock_site");
+
+    let companies_path = output_dir.join("companies.json");
+    let result = run_generator(
+        &site.base_url,
+        &output_dir,
+        &["--companies-output", companies_path.to_str().unwrap()],
+    );
+    assert_success(&result);
+
+    let output = read_output(&output_dir.join("dubInfo.json"));
+
+    // 101, 102 are on page 1, 104 on page 2 and 105, 106 on page 3.
+    // 103 has never been released and 107 is not dubbed at all.
+    assert_eq!(read_ids(&output, "dubbed"), [101, 102, 104, 105, 106]);
+    assert_eq!(read_ids(&output, "incomplete"), [102, 105]);
+
+    // 105 has been announced and 103 is listed, but has never been released
+    assert_eq!(read_ids(&output, "upcoming"), [105]);
+    assert_eq!(read_ids(&output, "neverReleased"), [103]);
+    assert!(read_ids(&output, "unknown").is_empty());
+
+    // Dubbed episodes compared to the episodes in the offline database
+    let progress = output["progress"].as_object().unwrap();
+    assert_eq!(progress["101"], 100);
+    assert_eq!(progress["102"], 52);
+    assert_eq!(progress["105"], 0);
+    assert!(!progress.contains_key("103"));
+
+    // Licensors, publishers and dubbing studios of the dubbed titles
+    let companies = read_output(&companies_path);
+    assert_eq!(read_ids(&companies["Kazé"], "licensor"), [104]);
+    assert_eq!(read_ids(&companies["Kazé"], "publisher"), [101, 104]);
+    assert_eq!(read_ids(&companies["Crunchyroll"], "publisher"), [104]);
+    assert_eq!(read_ids(&companies["Interopa Film"], "dubStudio"), [104]);
+    assert_eq!(read_ids(&companies["peppermint anime"], "publisher"), [102]);
+
+    std::fs::remove_dir_all(&output_dir).ok();
+}
+
+#[test]
+fn test_unmatched_entries_are_reported() {
+    let site = MockSite::start();
+    let output_dir = temp_dir("mock_site_unmatched");
+
+    let unmatched_path = output_dir.join("unmatched.json");
+    let result = run_generator(
+        &site.base_url,
+        &output_dir,
+        &["--unmatched-output", unmatched_path.to_str().unwrap()],
+    );
+    assert_success(&result);
+
+    let report = read_output(&unmatched_path);
+
+    // aniSearch 9 is listed on page 2, but not in the offline database
+    assert_eq!(
+        report["unmatched"],
+        serde_json::json!([{
+            "source": "aniSearch",
+            "url": "https://anisearch.com/anime/9",
+            "title": "Gintama",
+            "year": 2006,
+            "type": "TV-Series",
+        }])
+    );
+
+    // The page of aniSearch 6 names aniSearch 16 as its canonical URL
+    assert_eq!(
+        report["brokenLinks"],
+        serde_json::json!([{
+            "url": "https://anisearch.com/anime/6",
+            "malIds": [106],
+            "redirectsTo": "https://anisearch.com/anime/16",
+        }])
+    );
+
+    std::fs::remove_dir_all(&output_dir).ok();
+}
+
+#[test]
+fn test_fuzzy_matches_are_suggested() {
+    let site = MockSite::start();
+    let output_dir = temp_dir("mock_site_fuzzy");
+
+    // aniSearch 9 is not linked in the offline database, but MyAnimeList 109 has the same title, year and type
+    let review_path = output_dir.join("review.json");
+    let result = run_generator(
+        &site.base_url,
+        &output_dir,
+        &["--fuzzy-match", "--fuzzy-review-output", review_path.to_str().unwrap()],
+    );
+    assert_success(&result);
+
+    let review = read_output(&review_path);
+    assert_eq!(review[0]["url"], "https://anisearch.com/anime/9");
+    assert_eq!(
+        review[0]["candidates"][0],
+        serde_json::json!({
+            "malIds": [109],
+            "title": "Gintama",
+            "year": 2006,
+            "type": "TV",
+            "score": 1.0,
+        })
+    );
+
+    let output = read_output(&output_dir.join("dubInfo.json"));
+    assert_eq!(read_ids(&output, "dubbed"), [101, 102, 104, 105, 106]);
+
+    // With auto-apply, the match is linked instead of reviewed
+    let result = run_generator(
+        &site.base_url,
+        &output_dir,
+        &[
+            "--fuzzy-match",
+            "--fuzzy-auto-apply",
+            "0.9",
+            "--fuzzy-review-output",
+            review_path.to_str().unwrap(),
+        ],
+    );
+    assert_success(&result);
+
+    let review = read_output(&review_path);
+    assert_eq!(review, serde_json::json!([]));
+
+    let output = read_output(&output_dir.join("dubInfo.json"));
+    assert_eq!(read_ids(&output, "dubbed"), [101, 102, 104, 105, 106, 109]);
+    assert_eq!(read_ids(&output, "incomplete"), [102, 105, 109]);
+
+    std::fs::remove_dir_all(&output_dir).ok();
+}
+
+#[test]
+fn test_languages_are_written_separately() {
+    let site = MockSite::start();
+    let output_dir = temp_dir("mock_site_languages");
+
+    let combined_path = output_dir.join("combined.json");
+    let result = run_generator(
+        &site.base_url,
+        &output_dir,
+        &[
+            "--language",
+            "german,english",
+            "--combined-output",
+            combined_path.to_str().unwrap(),
+        ],
+    );
+    assert_success(&result);
+
+    // With several languages, the language code is part of the file name
+    assert!(!output_dir.join("dubInfo.json").exists());
+    assert!(output_dir.join("dubInfo.en.json").exists());
+
+    let german = read_output(&output_dir.join("dubInfo.de.json"));
+    assert_eq!(read_ids(&german, "dubbed"), [101, 102, 104, 105, 106]);
+    assert_eq!(read_ids(&german, "incomplete"), [102, 105]);
+
+    let combined = read_output(&combined_path);
+    assert_eq!(combined["de"], german);
+    assert!(combined["en"].is_object());
+
+    // Languages have to be in the language table
+    let result = run_generator(&site.base_url, &output_dir, &["--language", "xx"]);
+    assert_eq!(result.status.code(), Some(2));
+
+    std::fs::remove_dir_all(&output_dir).ok();
+}
+
+#[test]
+fn test_incremental_runs_reuse_complete_dubs() {
+    let site = MockSite::start();
+    let output_dir = temp_dir("mock_site_state");
+
+    let state_path = output_dir.join("state.json");
+    let state_args = ["--state", state_path.to_str().unwrap()];
+    let result = run_generator(&site.base_url, &output_dir, &state_args);
+    assert_success(&result);
+
+    let state = read_output(&state_path);
+    let entry = &state["entries"]["https://anisearch.com/anime/1"];
+    assert_eq!(entry["dubInfo"]["status"], "complete");
+    assert!(entry["dubInfo"]["contentHash"].is_string());
+
+    // aniSearch 1 and 4 are complete, while the others are incomplete or have moved
+    let result = run_generator(&site.base_url, &output_dir, &state_args);
+    assert_success(&result);
+    assert!(String::from_utf8_lossy(&result.stderr).contains("Reusing the dub info of 2 entries"));
+
+    let output = read_output(&output_dir.join("dubInfo.json"));
+    assert_eq!(read_ids(&output, "dubbed"), [101, 102, 104, 105, 106]);
+    assert_eq!(read_ids(&output, "incomplete"), [102, 105]);
+    assert_eq!(output["progress"]["101"], 100);
+
+    let result = run_generator(&site.base_url, &output_dir, &[&state_args[..], &["--full"]].concat());
+    assert_success(&result);
+    assert!(!String::from_utf8_lossy(&result.stderr).contains("Reusing the dub info"));
+
+    std::fs::remove_dir_all(&output_dir).ok();
+}
+
+#[test]
+fn test_changes_are_reported() {
+    let site = MockSite::start();
+    let output_dir = temp_dir("mock_site_changes");
+    let output_path = output_dir.join("dubInfo.json");
+
+    let result = run_generator(&site.base_url, &output_dir, &[]);
+    assert_success(&result);
+
+    // Without changes, --check succeeds
+    let result = run_generator(&site.base_url, &output_dir, &["--check"]);
+    assert_eq!(result.status.code(), Some(0));
+
+    let previous_output = r#"{"dubbed": [101, 102, 103, 105, 106], "incomplete": [101, 102]}"#;
+    std::fs::write(&output_path, previous_output).unwrap();
+
+    let changes_path = output_dir.join("changes.json");
+    let changes_markdown_path = output_dir.join("changes.md");
+    let result = run_generator(
+        &site.base_url,
+        &output_dir,
+        &[
+            "--check",
+            "--changes-output",
+            changes_path.to_str().unwrap(),
+            "--changes-markdown-output",
+            changes_markdown_path.to_str().unwrap(),
+        ],
+    );
+    assert_eq!(result.status.code(), Some(1));
+    assert_eq!(std::fs::read_to_string(&output_path).unwrap(), previous_output);
+
+    let changes = read_output(&changes_path);
+    assert_eq!(
+        changes["newlyDubbed"],
+        serde_json::json!([{"malId": 104, "title": "Death Note"}])
+    );
+    assert_eq!(
+        changes["noLongerDubbed"],
+        serde_json::json!([{"malId": 103, "title": "Candy Candy"}])
+    );
+    assert_eq!(changes["nowComplete"][0]["malId"], 101);
+    assert_eq!(changes["nowIncomplete"][0]["malId"], 105);
+
+    let markdown = std::fs::read_to_string(&changes_markdown_path).unwrap();
+    assert!(
+        markdown.starts_with("Update dub data: 1 newly dubbed, 1 no longer dubbed, 1 now complete, 1 now incomplete")
+    );
+    assert!(markdown.contains("## Newly dubbed\n\n- Death Note (https://myanimelist.net/anime/104)\n"));
+
+    std::fs::remove_dir_all(&output_dir).ok();
+}
+
+#[test]
+fn test_sanity_checks_prevent_data_loss() {
+    let site = MockSite::start();
+    let output_dir = temp_dir("mock_site_sanity_checks");
+    let output_path = output_dir.join("dubInfo.json");
+
+    // The mock site lists only a few of the previously dubbed titles
+    let previous_output = serde_json::json!({ "dubbed": (101..=200).collect::<Vec<u64>>(), "incomplete": [] });
+    std::fs::write(&output_path, previous_output.to_string()).unwrap();
+
+    let result = run_generator(&site.base_url, &output_dir, &[]);
+    assert_eq!(result.status.code(), Some(10));
+    assert!(String::from_utf8_lossy(&result.stderr).contains("95 titles are no longer dubbed"));
+    assert_eq!(
+        std::fs::read_to_string(&output_path).unwrap(),
+        previous_output.to_string()
+    );
+
+    let result = run_generator(&site.base_url, &output_dir, &["--force"]);
+    assert_success(&result);
+
+    let output = read_output(&output_path);
+    assert_eq!(read_ids(&output, "dubbed"), [101, 102, 104, 105, 106]);
+
+    let result = run_generator(&site.base_url, &output_dir, &["--min-dubbed", "6"]);
+    assert_eq!(result.status.code(), Some(10));
+
+    std::fs::remove_dir_all(&output_dir).ok();
+}
+
+#[test]
+fn test_missing_listing_page_aborts() {
+    let site = MockSite::start();
+    let output_dir = temp_dir("mock_site_listing_error");
+
+    let result = run_generator(&format!("{}/missing", site.base_url), &output_dir, &[]);
+
+    assert_eq!(result.status.code(), Some(3));
+    assert!(!output_dir.join("dubInfo.json").exists());
+
+    std::fs::remove_dir_all(&output_dir).ok();
+}
+
+#[test]
+fn test_aborted_runs_are_resumed() {
+    let site = MockSite::start();
+    let output_dir = temp_dir("mock_site_resume");
+    let flaky_url = format!("{}/flaky", site.base_url);
+    let checkpoint_path = output_dir.join("checkpoint.json");
+
+    // Every page fails once, so without retries the run is aborted on page 1 and then on pages 2 and 3
+    let result = run_generator(&flaky_url, &output_dir, &["--max-attempts", "1"]);
+    assert_eq!(result.status.code(), Some(3));
+
+    let result = run_generator(&flaky_url, &output_dir, &["--max-attempts", "1", "--resume"]);
+    assert_eq!(result.status.code(), Some(3));
+    assert!(!output_dir.join("dubInfo.json").exists());
+
+    let checkpoint = read_output(&checkpoint_path);
+    assert_eq!(
+        checkpoint["sources"]["aniSearch"]["visitedPages"],
+        serde_json::json!([1])
+    );
+
+    let result = run_generator(&flaky_url, &output_dir, &["--resume"]);
+    assert_success(&result);
+    assert!(String::from_utf8_lossy(&result.stderr).contains("Resuming with 1 of 3 dubbed anime pages checked"));
+    assert!(!checkpoint_path.exists());
+
+    let output = read_output(&output_dir.join("dubInfo.json"));
+    assert_eq!(read_ids(&output, "dubbed"), [101, 102, 104, 105, 106]);
+    assert_eq!(read_ids(&output, "incomplete"), [102, 105]);
+
+    std::fs::remove_dir_all(&output_dir).ok();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_interrupt_stops_waiting() {
+    // The crawl delay makes the run wait a minute before the second listing page
+    let site = MockSite::start_with_robots_txt(Some("User-agent: *\nCrawl-delay: 60\n"));
+    let output_dir = temp_dir("mock_site_interrupt");
+
+    let mut child = generator_command(&site.base_url, &output_dir, &[])
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_secs(2));
+
+    let start = std::time::Instant::now();
+    let killed = Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(killed.success());
+
+    let status = child.wait().unwrap();
+    assert_eq!(status.code(), Some(9));
+    assert!(start.elapsed() < std::time::Duration::from_secs(10));
+    assert!(output_dir.join("checkpoint.json").exists());
+
+    std::fs::remove_dir_all(&output_dir).ok();
+}
+
+#[test]
+fn test_failed_checks_are_unknown() {
+    let site = MockSite::start();
+    let output_dir = temp_dir("mock_site_unknown");
+    let flaky_url = format!("{}/flaky", site.base_url);
+    let args = ["--max-attempts", "1", "--resume"];
+
+    // Without retries, it takes three runs to get past the listing, after which every anime page fails once
+    for _ in 0..2 {
+        let result = run_generator(&flaky_url, &output_dir, &args);
+        assert_eq!(result.status.code(), Some(3));
+    }
+
+    let result = run_generator(&flaky_url, &output_dir, &args);
+    assert_success(&result);
+
+    // Titles with unknown status stay incomplete for consumers, which only know about `incomplete`
+    let output = read_output(&output_dir.join("dubInfo.json"));
+    assert_eq!(read_ids(&output, "dubbed"), [101, 102, 103, 104, 105, 106]);
+    assert_eq!(read_ids(&output, "incomplete"), [101, 102, 103, 104, 105, 106]);
+    assert_eq!(read_ids(&output, "unknown"), [101, 102, 103, 104, 105, 106]);
+    assert!(read_ids(&output, "upcoming").is_empty());
+
+    std::fs::remove_dir_all(&output_dir).ok();
+}
+
+#[test]
+fn test_robots_txt_disallow_aborts() {
+    let site = MockSite::start_with_robots_txt(Some("User-agent: mal_gerdubs\nDisallow: /anime/index\n"));
+    let output_dir = temp_dir("mock_site_robots_txt");
+
+    let result = run_generator(&site.base_url, &output_dir, &[]);
+
+    assert_eq!(result.status.code(), Some(7));
+    assert!(!output_dir.join("dubInfo.json").exists());
+
+    std::fs::remove_dir_all(&output_dir).ok();
+}
+
+#[test]
+fn test_failed_robots_txt_is_retried() {
+    let site = MockSite::start_with_robots_txt(Some("User-agent: *\nAllow: /\n"));
+    let output_dir = temp_dir("mock_site_robots_txt_retry");
+
+    let result = run_generator(&site.base_url, &output_dir, &[]);
+    assert_success(&result);
+
+    let output = read_output(&output_dir.join("dubInfo.json"));
+    assert_eq!(read_ids(&output, "dubbed"), [101, 102, 104, 105, 106]);
+
+    std::fs::remove_dir_all(&output_dir).ok();
+}
+
+#[test]
+fn test_failed_requests_are_retried() {
+    let site = MockSite::start();
+    let output_dir = temp_dir("mock_site_retry");
+
+    let result = run_generator(&format!("{}/flaky", site.base_url), &output_dir, &[]);
+    assert_success(&result);
+
+    let output = read_output(&output_dir.join("dubInfo.json"));
+    assert_eq!(read_ids(&output, "dubbed"), [101, 102, 104, 105, 106]);
+
+    std::fs::remove_dir_all(&output_dir).ok();
+}
+
+#[test]
+fn test_retries_are_bounded() {
+    let site = MockSite::start();
+    let output_dir = temp_dir("mock_site_unavailable");
+
+    let result = run_generator(
+        &format!("{}/unavailable", site.base_url),
+        &output_dir,
+        &["--max-attempts", "2"],
+    );
+
+    assert_eq!(result.status.code(), Some(3));
+    assert!(!output_dir.join("dubInfo.json").exists());
+
+    std::fs::remove_dir_all(&output_dir).ok();
+}
+
+#[test]
+fn test_second_source_is_merged() {
+    let site = MockSite::start();
+    let output_dir = temp_dir("mock_site_sources");
+
+    let provenance_path = output_dir.join("provenance.json");
+    let result = run_generator(
+        &site.base_url,
+        &output_dir,
+        &[
+            "--sources",
+            "anisearch,synchronkartei",
+            "--synchronkartei-url",
+            &format!("{}/sk", site.base_url),
+            "--provenance-output",
+            provenance_path.to_str().unwrap(),
+        ],
+    );
+    assert_success(&result);
+
+    // Captain Future (108) is only known to the Synchronkartei and only 40/52 episodes are dubbed
+    let output = read_output(&output_dir.join("dubInfo.json"));
+    assert_eq!(read_ids(&output, "dubbed"), [101, 102, 104, 105, 106, 108]);
+    assert_eq!(read_ids(&output, "incomplete"), [102, 105, 108]);
+
+    let provenance = read_output(&provenance_path);
+    assert_eq!(provenance["101"], serde_json::json!(["Synchronkartei", "aniSearch"]));
+    assert_eq!(provenance["102"], serde_json::json!(["aniSearch"]));
+    assert_eq!(provenance["108"], serde_json::json!(["Synchronkartei"]));
+
+    std::fs::remove_dir_all(&output_dir).ok();
+}
+
+#[test]
+fn test_overrides_are_applied() {
+    let site = MockSite::start();
+    let output_dir = temp_dir("mock_site_overrides");
+
+    let overrides_path = output_dir.join("overrides.toml");
+    std::fs::write(
+        &overrides_path,
+        r#"
+        [[override]]
+        mal = 107
+        status = "incomplete"
+        reason = "Dubbed, but missing on aniSearch"
+
+        [[override]]
+        anisearch = 2
+        status = "not-dubbed"
+        reason = "Only subtitled"
+
+        [[override]]
+        mal = 101
+        status = "dubbed"
+        reason = "Redundant, aniSearch agrees"
+
+        [[override]]
+        mal = 104
+        status = "not-dubbed"
+        reason = "Expired"
+        expires = 2000-01-01
+        "#,
+    )
+    .unwrap();
+
+    let result = run_generator(
+        &site.base_url,
+        &output_dir,
+        &["--overrides", overrides_path.to_str().unwrap()],
+    );
+    assert_success(&result);
+
+    let output = read_output(&output_dir.join("dubInfo.json"));
+    assert_eq!(read_ids(&output, "dubbed"), [101, 104, 105, 106, 107]);
+    assert_eq!(read_ids(&output, "incomplete"), [105, 107]);
+
+    std::fs::remove_dir_all(&output_dir).ok();
+}
+
+#[test]
+fn test_invalid_overrides_abort() {
+    let site = MockSite::start();
+    let output_dir = temp_dir("mock_site_invalid_overrides");
+
+    let overrides_path = output_dir.join("overrides.toml");
+    std::fs::write(&overrides_path, "[[override]]\nmal = 107\nstatus = \"dubbed\"\n").unwrap();
+
+    let result = run_generator(
+        &site.base_url,
+        &output_dir,
+        &["--overrides", overrides_path.to_str().unwrap()],
+    );
+
+    assert_eq!(result.status.code(), Some(5));
+
+    std::fs::remove_dir_all(&output_dir).ok();
+}
+
+#[test]
+fn test_mappings_are_applied() {
+    let site = MockSite::start();
+    let output_dir = temp_dir("mock_site_mappings");
+
+    let mappings_path = output_dir.join("mappings.toml");
+    std::fs::write(
+        &mappings_path,
+        r#"
+        [[mapping]]
+        anisearch = 1
+        action = "replace"
+        mal = [107]
+
+        [[mapping]]
+        anisearch = 2
+        action = "remove"
+        mal = [102]
+        "#,
+    )
+    .unwrap();
+
+    let result = run_generator(
+        &site.base_url,
+        &output_dir,
+        &["--mappings", mappings_path.to_str().unwrap()],
+    );
+    assert_success(&result);
+
+    let output = read_output(&output_dir.join("dubInfo.json"));
+    assert_eq!(read_ids(&output, "dubbed"), [104, 105, 106, 107]);
+    assert_eq!(read_ids(&output, "incomplete"), [105]);
+
+    // aniSearch 9 is not in the offline database, so it cannot be mapped
+    std::fs::write(
+        &mappings_path,
+        "[[mapping]]\nanisearch = 9\naction = \"add\"\nmal = [107]\n",
+    )
+    .unwrap();
+    let result = run_generator(
+        &site.base_url,
+        &output_dir,
+        &["--mappings", mappings_path.to_str().unwrap()],
+    );
+    assert_eq!(result.status.code(), Some(5));
+
+    std::fs::remove_dir_all(&output_dir).ok();
+}